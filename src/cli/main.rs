@@ -0,0 +1,328 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use image::ImageFormat;
+use realesrgan_rs::{Animation, BatchProcessor, ColorMatrix, ColorRange, FrameDeduplicator, Options, OptionsScaleFactor, RealEsrgan, Y4mReader, Y4mWriter};
+
+#[cfg(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime"))]
+use realesrgan_rs::OptionsModel;
+
+const USAGE: &str = "\
+Usage: realesrgan-rs -i infile -o outfile [options]...
+
+  -h                   show this help
+  -i input-path        input image path (jpg/png/webp/gif/y4m), directory, or - for y4m on stdin
+  -o output-path       output image path (jpg/png/webp/y4m), directory, or - for y4m on stdout
+  -s scale             upscale ratio (can be 2, 3, 4. default=4)
+  -t tile-size         tile size (>=32/0=auto, default=0)
+  -m model-path        folder path to the pre-trained models (default=embedded models)
+  -n model-name        model name (default=realesr-animevideov3, can be realesr-animevideov3 | realesrgan-x4plus | realesrgan-x4plus-anime)
+  -g gpu-id            gpu device to use (default=0)
+  -j load:proc:save    thread count for load/proc/save (default=1:2:2)
+  -r                   skip inputs whose output already exists
+  -d threshold         reuse the previous y4m frame when it differs by at most threshold (0-255)
+  -x                   enable tta mode
+  -f format            output image format (jpg/png/webp, default=ext/png)
+  -v                   verbose output";
+
+const DEFAULT_MODEL_NAME: &str = "realesr-animevideov3";
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug)]
+struct Arguments {
+    input: PathBuf,
+    output: PathBuf,
+    scale_factor: i32,
+    tilesize: usize,
+    model_path: Option<PathBuf>,
+    model_name: String,
+    gpuid: u8,
+    threads: (usize, usize, usize),
+    resume: bool,
+    duplicate_threshold: Option<f32>,
+    tta_mode: bool,
+    format: Option<ImageFormat>,
+    verbose: bool,
+}
+
+fn parse_format(format: &str) -> Result<ImageFormat, String> {
+    match ImageFormat::from_extension(format) {
+        Some(format) if format.can_write() => Ok(format),
+        _ => Err(format!("unsupported output format: {format}")),
+    }
+}
+
+fn parse_threads(threads: &str) -> Result<(usize, usize, usize), String> {
+    let counts = threads
+        .split(':')
+        .map(|count| count.parse::<usize>().ok().filter(|count| *count > 0))
+        .collect::<Option<Vec<_>>>();
+
+    match counts.as_deref() {
+        Some(&[load, proc, save]) => Ok((load, proc, save)),
+        _ => Err(format!("invalid thread count: {threads}")),
+    }
+}
+
+fn parse_arguments<I>(arguments: I) -> Result<Option<Arguments>, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut arguments = arguments.into_iter();
+
+    let mut input = None;
+    let mut output = None;
+    let mut scale_factor = 4;
+    let mut tilesize = 0;
+    let mut model_path = None;
+    let mut model_name = DEFAULT_MODEL_NAME.to_string();
+    let mut gpuid = 0;
+    let mut threads = (1, 2, 2);
+    let mut resume = false;
+    let mut duplicate_threshold = None;
+    let mut tta_mode = false;
+    let mut format = None;
+    let mut verbose = false;
+
+    while let Some(flag) = arguments.next() {
+        let mut value = || arguments.next().ok_or_else(|| format!("missing value for {flag}"));
+        match flag.as_str() {
+            "-h" => return Ok(None),
+            "-i" => input = Some(PathBuf::from(value()?)),
+            "-o" => output = Some(PathBuf::from(value()?)),
+            "-s" => scale_factor = value()?.parse().map_err(|_| "invalid scale".to_string())?,
+            "-t" => tilesize = value()?.parse().map_err(|_| "invalid tile size".to_string())?,
+            "-m" => model_path = Some(PathBuf::from(value()?)),
+            "-n" => model_name = value()?,
+            "-g" => gpuid = value()?.parse().map_err(|_| "invalid gpu id".to_string())?,
+            "-j" => threads = parse_threads(&value()?)?,
+            "-r" => resume = true,
+            "-d" => duplicate_threshold = Some(value()?.parse().map_err(|_| "invalid duplicate threshold".to_string())?),
+            "-x" => tta_mode = true,
+            "-f" => format = Some(parse_format(&value()?)?),
+            "-v" => verbose = true,
+            _ => return Err(format!("unknown option: {flag}")),
+        }
+    }
+
+    let input = input.ok_or("missing input path (-i)")?;
+    let output = output.ok_or("missing output path (-o)")?;
+
+    Ok(Some(Arguments {
+        input,
+        output,
+        scale_factor,
+        tilesize,
+        model_path,
+        model_name,
+        gpuid,
+        threads,
+        resume,
+        duplicate_threshold,
+        tta_mode,
+        format,
+        verbose,
+    }))
+}
+
+fn model_files(model_path: &Path, model_name: &str, scale_factor: i32) -> Result<(PathBuf, PathBuf), String> {
+    let names = [model_name.to_string(), format!("{model_name}-x{scale_factor}")];
+    names
+        .iter()
+        .map(|name| (model_path.join(format!("{name}.param")), model_path.join(format!("{name}.bin"))))
+        .find(|(param, bin)| param.exists() && bin.exists())
+        .ok_or_else(|| format!("model {model_name} not found in {}", model_path.display()))
+}
+
+fn options(arguments: &Arguments) -> Result<Options<'static>, String> {
+    let options = Options::default()
+        .gpuid(arguments.gpuid)
+        .tta_mode(arguments.tta_mode)
+        .tilesize(arguments.tilesize);
+
+    if let Some(model_path) = &arguments.model_path {
+        let scale_factor = OptionsScaleFactor::try_from(arguments.scale_factor)
+            .map_err(|scale_factor| format!("invalid scale: {scale_factor}"))?;
+        let (param, bin) = model_files(model_path, &arguments.model_name, arguments.scale_factor)?;
+        return options
+            .scale_factor(scale_factor)
+            .model_files(param, bin)
+            .map_err(|error| error.to_string());
+    }
+
+    #[cfg(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime"))]
+    {
+        let model = OptionsModel::from_name(&arguments.model_name, arguments.scale_factor).ok_or_else(|| {
+            match OptionsModel::ALL.iter().find(|model| model.name() == arguments.model_name) {
+                Some(model) => format!("model {} only upscales {}x, not {}x", arguments.model_name, model.get_scale_factor(), arguments.scale_factor),
+                None => format!("unknown model {} for scale {}", arguments.model_name, arguments.scale_factor),
+            }
+        })?;
+        Ok(options.model(model))
+    }
+
+    #[cfg(not(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime")))]
+    Err("no embedded models available, pass a model path with -m".to_string())
+}
+
+fn output_format(path: &Path, format: Option<ImageFormat>) -> ImageFormat {
+    format
+        .or_else(|| ImageFormat::from_path(path).ok())
+        .unwrap_or(ImageFormat::Png)
+}
+
+fn upscale_file(realesrgan: &RealEsrgan, input: &Path, output: &Path, format: ImageFormat) -> Result<(), String> {
+    if let Some(animation) = Animation::open(input).map_err(|error| error.to_string())? {
+        let animation = realesrgan.process_animation(&animation).map_err(|error| error.to_string())?;
+        return animation.save_with_format(output, format).map_err(|error| error.to_string());
+    }
+
+    if ImageFormat::from_path(output).is_ok_and(|extension| extension == format) {
+        return realesrgan.process_file_to(input, output).map_err(|error| error.to_string());
+    }
+
+    let image = realesrgan.process_file(input).map_err(|error| error.to_string())?;
+    image
+        .save_with_format(output, format)
+        .map_err(|error| format!("failed to save {}: {error}", output.display()))
+}
+
+fn is_y4m(path: &Path) -> bool {
+    path.as_os_str() == "-" || path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("y4m"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Y4m,
+    File,
+    Directory,
+}
+
+fn mode(input: &Path) -> Mode {
+    if is_y4m(input) {
+        Mode::Y4m
+    } else if input.is_dir() {
+        Mode::Directory
+    } else {
+        Mode::File
+    }
+}
+
+fn open_y4m(path: &Path) -> Result<Box<dyn Read>, String> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let file = std::fs::File::open(path).map_err(|error| format!("failed to open {}: {error}", path.display()))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+fn create_y4m(path: &Path) -> Result<Box<dyn Write>, String> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(BufWriter::new(std::io::stdout().lock())));
+    }
+    let file = std::fs::File::create(path).map_err(|error| format!("failed to create {}: {error}", path.display()))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+/// Y4M carries no matrix, so follow the usual convention of BT.709 for HD and BT.601 below it.
+fn upscale_y4m(realesrgan: &RealEsrgan, arguments: &Arguments) -> Result<(), String> {
+    let mut reader = Y4mReader::new(open_y4m(&arguments.input)?).map_err(|error| error.to_string())?;
+    let header = reader.header().clone();
+    let matrix = if header.height >= 720 { ColorMatrix::Bt709 } else { ColorMatrix::Bt601 };
+    let range = header.range.unwrap_or(ColorRange::Limited);
+
+    let mut writer = Y4mWriter::new(create_y4m(&arguments.output)?, header.scaled(realesrgan.output_scale())).map_err(|error| error.to_string())?;
+    let mut deduplicator = FrameDeduplicator::new(arguments.duplicate_threshold);
+    let mut frames = 0;
+    while let Some(frame) = reader.read_frame().map_err(|error| error.to_string())? {
+        let (upscaled, duplicate) = deduplicator
+            .process(&frame.data, |_| realesrgan.process_yuv(&frame, matrix, range))
+            .map_err(|error| error.to_string())?;
+        writer.write_frame(upscaled).map_err(|error| error.to_string())?;
+        frames += 1;
+        if arguments.verbose {
+            eprintln!("frame {frames} {}", if duplicate { "skipped" } else { "done" });
+        }
+    }
+
+    if arguments.verbose && deduplicator.skipped() > 0 {
+        eprintln!("{} of {frames} frames skipped as duplicates", deduplicator.skipped());
+    }
+    writer.flush().map_err(|error| error.to_string())
+}
+
+fn run(arguments: Arguments) -> Result<(), String> {
+    let mode = mode(&arguments.input);
+    if mode == Mode::Y4m {
+        let realesrgan = RealEsrgan::new(options(&arguments)?).map_err(|error| error.to_string())?;
+        return upscale_y4m(&realesrgan, &arguments);
+    }
+
+    if mode == Mode::File {
+        let realesrgan = RealEsrgan::new(options(&arguments)?).map_err(|error| error.to_string())?;
+        let format = output_format(&arguments.output, arguments.format);
+        upscale_file(&realesrgan, &arguments.input, &arguments.output, format)?;
+        if arguments.verbose {
+            eprintln!("{} -> {} done", arguments.input.display(), arguments.output.display());
+        }
+        return Ok(());
+    }
+
+    let (load_threads, proc_threads, save_threads) = arguments.threads;
+    let mut batch = BatchProcessor::new(RealEsrgan::new(options(&arguments)?).map_err(|error| error.to_string())?);
+    for _ in 1..proc_threads {
+        batch = batch.instance(RealEsrgan::new(options(&arguments)?).map_err(|error| error.to_string())?);
+    }
+
+    let report = batch
+        .load_threads(load_threads)
+        .save_threads(save_threads)
+        .format(arguments.format.unwrap_or(ImageFormat::Png))
+        .skip_existing(arguments.resume)
+        .run(&arguments.input, &arguments.output)
+        .map_err(|error| error.to_string())?;
+
+    if arguments.verbose {
+        for input in &report.processed {
+            eprintln!("{} done", input.display());
+        }
+        for input in &report.skipped {
+            eprintln!("{} skipped", input.display());
+        }
+    }
+
+    for (input, error) in &report.failed {
+        eprintln!("{}: {error}", input.display());
+    }
+
+    if report.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} of {} files failed to process", report.failed.len(), report.failed.len() + report.processed.len()))
+    }
+}
+
+fn main() -> ExitCode {
+    let arguments = match parse_arguments(std::env::args().skip(1)) {
+        Ok(Some(arguments)) => arguments,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(arguments) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+fn parse(arguments: &str) -> Result<Option<Arguments>, String> {
+    parse_arguments(arguments.split_whitespace().map(str::to_string))
+}
+
+#[test]
+fn parses_flags() {
+    let arguments = parse("-i in.png -o out.webp -s 2 -t 64 -n realesrgan-x4plus -g 1 -j 2:3:4 -r -d 1.5 -x -f webp -v")
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(arguments.input, Path::new("in.png"));
+    assert_eq!(arguments.output, Path::new("out.webp"));
+    assert_eq!(arguments.scale_factor, 2);
+    assert_eq!(arguments.tilesize, 64);
+    assert_eq!(arguments.model_name, "realesrgan-x4plus");
+    assert_eq!(arguments.gpuid, 1);
+    assert_eq!(arguments.threads, (2, 3, 4));
+    assert_eq!(arguments.duplicate_threshold, Some(1.5));
+    assert_eq!(arguments.format, Some(ImageFormat::WebP));
+    assert!(arguments.resume && arguments.tta_mode && arguments.verbose);
+}
+
+#[test]
+fn defaults_match_upstream() {
+    let arguments = parse("-i in.png -o out.png").unwrap().unwrap();
+
+    assert_eq!(arguments.scale_factor, 4);
+    assert_eq!(arguments.tilesize, 0);
+    assert_eq!(arguments.model_name, DEFAULT_MODEL_NAME);
+    assert_eq!(arguments.threads, (1, 2, 2));
+    assert_eq!(arguments.model_path, None);
+    assert!(!arguments.resume && !arguments.tta_mode && !arguments.verbose);
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    assert!(parse("-h").unwrap().is_none());
+    assert!(parse("-o out.png").is_err());
+    assert!(parse("-i in.png").is_err());
+    assert!(parse("-i in.png -o out.png -s").is_err());
+    assert!(parse("-i in.png -o out.png -s two").is_err());
+    assert!(parse("-i in.png -o out.png -j 1:2").is_err());
+    assert!(parse("-i in.png -o out.png -j 1:0:2").is_err());
+    assert!(parse("-i in.png -o out.png -f psd").is_err());
+    assert!(parse("-i in.png -o out.png -q").is_err());
+}
+
+#[test]
+fn splits_files_and_directories() {
+    let root = std::env::temp_dir().join("realesrgan_rs_cli_mode");
+    std::fs::create_dir_all(&root).unwrap();
+
+    assert_eq!(mode(&root), Mode::Directory);
+    assert_eq!(mode(&root.join("image.png")), Mode::File);
+    assert_eq!(mode(Path::new("-")), Mode::Y4m);
+    assert_eq!(mode(Path::new("clip.Y4M")), Mode::Y4m);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+#[cfg(feature = "model-realesrgan-plus")]
+fn rejects_scale_the_model_cannot_do() {
+    let arguments = parse("-i in.png -o out.png -n realesrgan-x4plus -s 2").unwrap().unwrap();
+    let error = options(&arguments).unwrap_err();
+    assert_eq!(error, "model realesrgan-x4plus only upscales 4x, not 2x");
+
+    let arguments = parse("-i in.png -o out.png -n realesrgan-x4plus").unwrap().unwrap();
+    assert!(options(&arguments).is_ok());
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn resolves_bare_model_names_by_scale() {
+    let arguments = parse("-i in.png -o out.png -s 3").unwrap().unwrap();
+    assert_eq!(options(&arguments).unwrap().scale_factor, 3);
+
+    let arguments = parse("-i in.png -o out.png -n unknown-model").unwrap().unwrap();
+    assert!(options(&arguments).unwrap_err().starts_with("unknown model"));
+}
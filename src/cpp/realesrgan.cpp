@@ -2,7 +2,8 @@
 
 #include "realesrgan.h"
 
-#include <algorithm>
+#include "ncnn/modelbin.h"
+
 #include <vector>
 
 static const uint32_t realesrgan_preproc_spv_data[] = {
@@ -43,15 +44,50 @@ static const uint32_t realesrgan_postproc_tta_int8s_spv_data[] = {
     #include "realesrgan_postproc_tta_int8s.spv.hex.h"
 };
 
-RealESRGAN::RealESRGAN(int gpuid, bool _tta_mode)
+RealESRGAN::RealESRGAN(int gpuid, bool _tta_mode, int precision)
 {
     net.opt.use_vulkan_compute = true;
-    net.opt.use_fp16_packed = true;
-    net.opt.use_fp16_storage = true;
-    net.opt.use_fp16_arithmetic = false;
-    net.opt.use_int8_storage = true;
     net.opt.use_int8_arithmetic = false;
 
+    // the preproc/postproc shader variant in load_files follows these flags
+    switch (precision)
+    {
+    case REALESRGAN_PRECISION_FP32:
+        net.opt.use_fp16_packed = false;
+        net.opt.use_fp16_storage = false;
+        net.opt.use_fp16_arithmetic = false;
+        net.opt.use_int8_storage = false;
+        break;
+    case REALESRGAN_PRECISION_FP16_STORAGE:
+        net.opt.use_fp16_packed = true;
+        net.opt.use_fp16_storage = true;
+        net.opt.use_fp16_arithmetic = false;
+        net.opt.use_int8_storage = false;
+        break;
+    case REALESRGAN_PRECISION_FP16_ARITHMETIC:
+        net.opt.use_fp16_packed = true;
+        net.opt.use_fp16_storage = true;
+        net.opt.use_fp16_arithmetic = true;
+        net.opt.use_int8_storage = false;
+        break;
+    default:
+        net.opt.use_fp16_packed = true;
+        net.opt.use_fp16_storage = true;
+        net.opt.use_fp16_arithmetic = false;
+        net.opt.use_int8_storage = true;
+        break;
+    }
+
+    // never pick a shader variant the device cannot run
+    if (gpuid >= 0)
+    {
+        const ncnn::GpuInfo& info = ncnn::get_gpu_info(gpuid);
+        net.opt.use_fp16_packed = net.opt.use_fp16_packed && info.support_fp16_packed();
+        net.opt.use_fp16_storage = net.opt.use_fp16_storage && info.support_fp16_storage();
+        net.opt.use_fp16_arithmetic = net.opt.use_fp16_arithmetic && info.support_fp16_arithmetic();
+        net.opt.use_int8_storage = net.opt.use_int8_storage && info.support_int8_storage();
+    }
+
     net.set_vulkan_device(gpuid);
 
     realesrgan_preproc = 0;
@@ -70,29 +106,96 @@ RealESRGAN::~RealESRGAN()
         delete realesrgan_postproc;
     }
 
-    bicubic_2x->destroy_pipeline(net.opt);
-    delete bicubic_2x;
+    // load_files may have bailed out before creating these
+    if (bicubic_2x)
+    {
+        bicubic_2x->destroy_pipeline(net.opt);
+        delete bicubic_2x;
+    }
 
-    bicubic_3x->destroy_pipeline(net.opt);
-    delete bicubic_3x;
+    if (bicubic_3x)
+    {
+        bicubic_3x->destroy_pipeline(net.opt);
+        delete bicubic_3x;
+    }
 
-    bicubic_4x->destroy_pipeline(net.opt);
-    delete bicubic_4x;
+    if (bicubic_4x)
+    {
+        bicubic_4x->destroy_pipeline(net.opt);
+        delete bicubic_4x;
+    }
+}
+
+// stdio reader that remembers whether the model ran past the end of the file
+class CheckedDataReader : public ncnn::DataReaderFromStdio
+{
+public:
+    CheckedDataReader(FILE* fp) : ncnn::DataReaderFromStdio(fp), short_read(false) {}
+
+    virtual size_t read(void* buf, size_t size) const
+    {
+        size_t nread = ncnn::DataReaderFromStdio::read(buf, size);
+        if (nread != size)
+            short_read = true;
+        return nread;
+    }
+
+public:
+    mutable bool short_read;
+};
+
+// reload the weights layer by layer to find the first one that runs past the end of the file
+static std::string find_short_read_layer(const ncnn::Net& net, FILE* bin, long start)
+{
+    if (fseek(bin, start, SEEK_SET) != 0)
+        return std::string();
+
+    CheckedDataReader dr(bin);
+    ncnn::ModelBinFromDataReader mb(dr);
+
+    const std::vector<ncnn::Layer*>& layers = net.layers();
+    for (size_t i = 0; i < layers.size(); i++)
+    {
+        layers[i]->load_model(mb);
+        if (dr.short_read)
+            return layers[i]->name;
+    }
+
+    return std::string();
 }
 
 int RealESRGAN::load_files(FILE *param, FILE *bin)
 {
-    net.load_param(param);
-    net.load_model(bin);
+    error_layer.clear();
+
+    if (net.load_param(param) != 0)
+        return REALESRGAN_ERROR_PARAM_PARSE_FAILED;
+
+    {
+        const long start = ftell(bin);
+
+        CheckedDataReader dr(bin);
+        int ret = net.load_model(dr);
+
+        if (dr.short_read)
+        {
+            error_layer = find_short_read_layer(net, bin, start);
+            return REALESRGAN_ERROR_WEIGHT_SIZE_MISMATCH;
+        }
+
+        // more weights than the param file describes
+        if (ret == 0 && fgetc(bin) != EOF)
+            return REALESRGAN_ERROR_WEIGHT_SIZE_MISMATCH;
+
+        // the weights were all there, so a layer failed to build its pipeline
+        if (ret != 0)
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
+    }
 
     // initialize preprocess and postprocess pipeline
     {
-        std::vector<ncnn::vk_specialization_type> specializations(1);
-#if _WIN32
-        specializations[0].i = 1;
-#else
-        specializations[0].i = 0;
-#endif
+        // channel order is a push constant, so one pipeline serves both RGB and BGR
+        std::vector<ncnn::vk_specialization_type> specializations;
 
         realesrgan_preproc = new ncnn::Pipeline(net.vulkan_device());
         realesrgan_preproc->set_optimal_local_size_xyz(32, 32, 3);
@@ -100,37 +203,52 @@ int RealESRGAN::load_files(FILE *param, FILE *bin)
         realesrgan_postproc = new ncnn::Pipeline(net.vulkan_device());
         realesrgan_postproc->set_optimal_local_size_xyz(32, 32, 3);
 
+        int preproc_ret = 0;
+        int postproc_ret = 0;
+
         if (tta_mode)
         {
             if (net.opt.use_fp16_storage && net.opt.use_int8_storage)
-                realesrgan_preproc->create(realesrgan_preproc_tta_int8s_spv_data, sizeof(realesrgan_preproc_tta_int8s_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_tta_int8s_spv_data, sizeof(realesrgan_preproc_tta_int8s_spv_data), specializations);
             else if (net.opt.use_fp16_storage)
-                realesrgan_preproc->create(realesrgan_preproc_tta_fp16s_spv_data, sizeof(realesrgan_preproc_tta_fp16s_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_tta_fp16s_spv_data, sizeof(realesrgan_preproc_tta_fp16s_spv_data), specializations);
             else
-                realesrgan_preproc->create(realesrgan_preproc_tta_spv_data, sizeof(realesrgan_preproc_tta_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_tta_spv_data, sizeof(realesrgan_preproc_tta_spv_data), specializations);
 
             if (net.opt.use_fp16_storage && net.opt.use_int8_storage)
-                realesrgan_postproc->create(realesrgan_postproc_tta_int8s_spv_data, sizeof(realesrgan_postproc_tta_int8s_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_tta_int8s_spv_data, sizeof(realesrgan_postproc_tta_int8s_spv_data), specializations);
             else if (net.opt.use_fp16_storage)
-                realesrgan_postproc->create(realesrgan_postproc_tta_fp16s_spv_data, sizeof(realesrgan_postproc_tta_fp16s_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_tta_fp16s_spv_data, sizeof(realesrgan_postproc_tta_fp16s_spv_data), specializations);
             else
-                realesrgan_postproc->create(realesrgan_postproc_tta_spv_data, sizeof(realesrgan_postproc_tta_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_tta_spv_data, sizeof(realesrgan_postproc_tta_spv_data), specializations);
         }
         else
         {
             if (net.opt.use_fp16_storage && net.opt.use_int8_storage)
-                realesrgan_preproc->create(realesrgan_preproc_int8s_spv_data, sizeof(realesrgan_preproc_int8s_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_int8s_spv_data, sizeof(realesrgan_preproc_int8s_spv_data), specializations);
             else if (net.opt.use_fp16_storage)
-                realesrgan_preproc->create(realesrgan_preproc_fp16s_spv_data, sizeof(realesrgan_preproc_fp16s_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_fp16s_spv_data, sizeof(realesrgan_preproc_fp16s_spv_data), specializations);
             else
-                realesrgan_preproc->create(realesrgan_preproc_spv_data, sizeof(realesrgan_preproc_spv_data), specializations);
+                preproc_ret = realesrgan_preproc->create(realesrgan_preproc_spv_data, sizeof(realesrgan_preproc_spv_data), specializations);
 
             if (net.opt.use_fp16_storage && net.opt.use_int8_storage)
-                realesrgan_postproc->create(realesrgan_postproc_int8s_spv_data, sizeof(realesrgan_postproc_int8s_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_int8s_spv_data, sizeof(realesrgan_postproc_int8s_spv_data), specializations);
             else if (net.opt.use_fp16_storage)
-                realesrgan_postproc->create(realesrgan_postproc_fp16s_spv_data, sizeof(realesrgan_postproc_fp16s_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_fp16s_spv_data, sizeof(realesrgan_postproc_fp16s_spv_data), specializations);
             else
-                realesrgan_postproc->create(realesrgan_postproc_spv_data, sizeof(realesrgan_postproc_spv_data), specializations);
+                postproc_ret = realesrgan_postproc->create(realesrgan_postproc_spv_data, sizeof(realesrgan_postproc_spv_data), specializations);
+        }
+
+        if (preproc_ret != 0)
+        {
+            error_layer = "realesrgan_preproc";
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
+        }
+
+        if (postproc_ret != 0)
+        {
+            error_layer = "realesrgan_postproc";
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
         }
     }
 
@@ -145,7 +263,11 @@ int RealESRGAN::load_files(FILE *param, FILE *bin)
         pd.set(2, 2.f);
         bicubic_2x->load_param(pd);
 
-        bicubic_2x->create_pipeline(net.opt);
+        if (bicubic_2x->create_pipeline(net.opt) != 0)
+        {
+            error_layer = "bicubic_2x";
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
+        }
     }
     {
         bicubic_3x = ncnn::create_layer("Interp");
@@ -157,7 +279,11 @@ int RealESRGAN::load_files(FILE *param, FILE *bin)
         pd.set(2, 3.f);
         bicubic_3x->load_param(pd);
 
-        bicubic_3x->create_pipeline(net.opt);
+        if (bicubic_3x->create_pipeline(net.opt) != 0)
+        {
+            error_layer = "bicubic_3x";
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
+        }
     }
     {
         bicubic_4x = ncnn::create_layer("Interp");
@@ -169,396 +295,313 @@ int RealESRGAN::load_files(FILE *param, FILE *bin)
         pd.set(2, 4.f);
         bicubic_4x->load_param(pd);
 
-        bicubic_4x->create_pipeline(net.opt);
+        if (bicubic_4x->create_pipeline(net.opt) != 0)
+        {
+            error_layer = "bicubic_4x";
+            return REALESRGAN_ERROR_PIPELINE_CREATION_FAILED;
+        }
     }
 
     return 0;
 }
 
-int RealESRGAN::process(const ncnn::Mat& inimage, ncnn::Mat& outimage) const
+int RealESRGAN::process(const ncnn::Mat& inimage, ncnn::Mat& outimage, bool bgr) const
 {
+    // tiling and context padding are handled by the caller, the input is upscaled in one piece
     const unsigned char* pixeldata = (const unsigned char*)inimage.data;
     const int w = inimage.w;
     const int h = inimage.h;
     const int channels = inimage.elempack;
 
-    const int TILE_SIZE_X = tilesize;
-    const int TILE_SIZE_Y = tilesize;
-
     ncnn::VkAllocator* blob_vkallocator = net.vulkan_device()->acquire_blob_allocator();
     ncnn::VkAllocator* staging_vkallocator = net.vulkan_device()->acquire_staging_allocator();
 
+    // hand the allocators back on every return path
+    struct AllocatorGuard
+    {
+        const ncnn::VulkanDevice* vkdev;
+        ncnn::VkAllocator* blob;
+        ncnn::VkAllocator* staging;
+
+        ~AllocatorGuard()
+        {
+            vkdev->reclaim_blob_allocator(blob);
+            vkdev->reclaim_staging_allocator(staging);
+        }
+    } allocator_guard = { net.vulkan_device(), blob_vkallocator, staging_vkallocator };
+
     ncnn::Option opt = net.opt;
     opt.blob_vkallocator = blob_vkallocator;
     opt.workspace_vkallocator = blob_vkallocator;
     opt.staging_vkallocator = staging_vkallocator;
 
-    // each tile 100x100
-    const int xtiles = (w + TILE_SIZE_X - 1) / TILE_SIZE_X;
-    const int ytiles = (h + TILE_SIZE_Y - 1) / TILE_SIZE_Y;
-
     const size_t in_out_tile_elemsize = opt.use_fp16_storage ? 2u : 4u;
 
-    //#pragma omp parallel for num_threads(2)
-    for (int yi = 0; yi < ytiles; yi++)
+    ncnn::Mat in;
+    if (opt.use_fp16_storage && opt.use_int8_storage)
     {
-        const int tile_h_nopad = std::min((yi + 1) * TILE_SIZE_Y, h) - yi * TILE_SIZE_Y;
-
-        int in_tile_y0 = std::max(yi * TILE_SIZE_Y - prepadding, 0);
-        int in_tile_y1 = std::min((yi + 1) * TILE_SIZE_Y + prepadding, h);
-
-        ncnn::Mat in;
-        if (opt.use_fp16_storage && opt.use_int8_storage)
+        in = ncnn::Mat(w, h, (unsigned char*)pixeldata, (size_t)channels, 1);
+    }
+    else
+    {
+        if (channels == 3)
         {
-            in = ncnn::Mat(w, (in_tile_y1 - in_tile_y0), (unsigned char*)pixeldata + in_tile_y0 * w * channels, (size_t)channels, 1);
+            in = ncnn::Mat::from_pixels(pixeldata, bgr ? ncnn::Mat::PIXEL_BGR2RGB : ncnn::Mat::PIXEL_RGB, w, h);
         }
-        else
+        if (channels == 4)
         {
-            if (channels == 3)
-            {
-#if _WIN32
-                in = ncnn::Mat::from_pixels(pixeldata + in_tile_y0 * w * channels, ncnn::Mat::PIXEL_BGR2RGB, w, (in_tile_y1 - in_tile_y0));
-#else
-                in = ncnn::Mat::from_pixels(pixeldata + in_tile_y0 * w * channels, ncnn::Mat::PIXEL_RGB, w, (in_tile_y1 - in_tile_y0));
-#endif
-            }
-            if (channels == 4)
+            in = ncnn::Mat::from_pixels(pixeldata, bgr ? ncnn::Mat::PIXEL_BGRA2RGBA : ncnn::Mat::PIXEL_RGBA, w, h);
+        }
+    }
+
+    ncnn::VkCompute cmd(net.vulkan_device());
+
+    // upload
+    ncnn::VkMat in_gpu;
+    cmd.record_clone(in, in_gpu, opt);
+    if (in_gpu.empty())
+        return REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY;
+
+    ncnn::VkMat out_gpu;
+    if (opt.use_fp16_storage && opt.use_int8_storage)
+    {
+        out_gpu.create(w * scale, h * scale, (size_t)channels, 1, blob_vkallocator);
+    }
+    else
+    {
+        out_gpu.create(w * scale, h * scale, channels, (size_t)4u, 1, blob_vkallocator);
+    }
+    if (out_gpu.empty())
+        return REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY;
+
+    ncnn::VkMat in_alpha_tile_gpu;
+    if (channels == 4)
+    {
+        in_alpha_tile_gpu.create(w, h, 1, in_out_tile_elemsize, 1, blob_vkallocator);
+        if (in_alpha_tile_gpu.empty())
+            return REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+
+    // the shaders still take tile offsets and padding, which are all zero for a single tile
+    std::vector<ncnn::vk_constant_type> preproc_constants(14);
+    preproc_constants[0].i = in_gpu.w;
+    preproc_constants[1].i = in_gpu.h;
+    preproc_constants[2].i = in_gpu.cstep;
+    preproc_constants[6].i = 0;
+    preproc_constants[7].i = 0;
+    preproc_constants[8].i = 0;
+    preproc_constants[9].i = 0;
+    preproc_constants[10].i = channels;
+    preproc_constants[11].i = in_alpha_tile_gpu.w;
+    preproc_constants[12].i = in_alpha_tile_gpu.h;
+    preproc_constants[13].i = bgr ? 1 : 0;
+
+    std::vector<ncnn::vk_constant_type> postproc_constants(14);
+    postproc_constants[3].i = out_gpu.w;
+    postproc_constants[4].i = out_gpu.h;
+    postproc_constants[5].i = out_gpu.cstep;
+    postproc_constants[6].i = 0;
+    postproc_constants[7].i = out_gpu.w;
+    postproc_constants[8].i = 0;
+    postproc_constants[9].i = 0;
+    postproc_constants[10].i = channels;
+    postproc_constants[13].i = bgr ? 1 : 0;
+
+    ncnn::VkMat dispatcher;
+    dispatcher.c = channels;
+
+    if (tta_mode)
+    {
+        // preproc
+        ncnn::VkMat in_tile_gpu[8];
+        {
+            for (int ti = 0; ti < 8; ti++)
             {
-#if _WIN32
-                in = ncnn::Mat::from_pixels(pixeldata + in_tile_y0 * w * channels, ncnn::Mat::PIXEL_BGRA2RGBA, w, (in_tile_y1 - in_tile_y0));
-#else
-                in = ncnn::Mat::from_pixels(pixeldata + in_tile_y0 * w * channels, ncnn::Mat::PIXEL_RGBA, w, (in_tile_y1 - in_tile_y0));
-#endif
+                // the last four variants are transposed
+                if (ti < 4)
+                    in_tile_gpu[ti].create(w, h, 3, in_out_tile_elemsize, 1, blob_vkallocator);
+                else
+                    in_tile_gpu[ti].create(h, w, 3, in_out_tile_elemsize, 1, blob_vkallocator);
+
+                if (in_tile_gpu[ti].empty())
+                    return REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY;
             }
-        }
 
-        ncnn::VkCompute cmd(net.vulkan_device());
+            std::vector<ncnn::VkMat> bindings(10);
+            bindings[0] = in_gpu;
+            for (int ti = 0; ti < 8; ti++)
+                bindings[ti + 1] = in_tile_gpu[ti];
+            bindings[9] = in_alpha_tile_gpu;
+
+            preproc_constants[3].i = in_tile_gpu[0].w;
+            preproc_constants[4].i = in_tile_gpu[0].h;
+            preproc_constants[5].i = in_tile_gpu[0].cstep;
+
+            dispatcher.w = in_tile_gpu[0].w;
+            dispatcher.h = in_tile_gpu[0].h;
+
+            cmd.record_pipeline(realesrgan_preproc, bindings, preproc_constants, dispatcher);
+        }
 
-        // upload
-        ncnn::VkMat in_gpu;
+        // realesrgan
+        ncnn::VkMat out_tile_gpu[8];
+        for (int ti = 0; ti < 8; ti++)
         {
-            cmd.record_clone(in, in_gpu, opt);
+            ncnn::Extractor ex = net.create_extractor();
+
+            ex.set_blob_vkallocator(blob_vkallocator);
+            ex.set_workspace_vkallocator(blob_vkallocator);
+            ex.set_staging_vkallocator(staging_vkallocator);
+
+            ex.input("data", in_tile_gpu[ti]);
+
+            int ret = ex.extract("output", out_tile_gpu[ti], cmd);
+            if (ret != 0)
+                return ret == -100 ? REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY : REALESRGAN_ERROR_PROCESSING_FAILED;
 
-            if (xtiles > 1)
             {
-                cmd.submit_and_wait();
+                if (cmd.submit_and_wait() != 0)
+                    return REALESRGAN_ERROR_DEVICE_LOST;
                 cmd.reset();
             }
         }
 
-        int out_tile_y0 = std::max(yi * TILE_SIZE_Y, 0);
-        int out_tile_y1 = std::min((yi + 1) * TILE_SIZE_Y, h);
+        ncnn::VkMat out_alpha_tile_gpu = upscale_alpha(in_alpha_tile_gpu, cmd, opt);
 
-        ncnn::VkMat out_gpu;
-        if (opt.use_fp16_storage && opt.use_int8_storage)
+        // postproc
         {
-            out_gpu.create(w * scale, (out_tile_y1 - out_tile_y0) * scale, (size_t)channels, 1, blob_vkallocator);
+            std::vector<ncnn::VkMat> bindings(10);
+            for (int ti = 0; ti < 8; ti++)
+                bindings[ti] = out_tile_gpu[ti];
+            bindings[8] = out_alpha_tile_gpu;
+            bindings[9] = out_gpu;
+
+            postproc_constants[0].i = out_tile_gpu[0].w;
+            postproc_constants[1].i = out_tile_gpu[0].h;
+            postproc_constants[2].i = out_tile_gpu[0].cstep;
+            postproc_constants[11].i = out_alpha_tile_gpu.w;
+            postproc_constants[12].i = out_alpha_tile_gpu.h;
+
+            dispatcher.w = out_gpu.w;
+            dispatcher.h = out_gpu.h;
+
+            cmd.record_pipeline(realesrgan_postproc, bindings, postproc_constants, dispatcher);
         }
-        else
+    }
+    else
+    {
+        // preproc
+        ncnn::VkMat in_tile_gpu;
         {
-            out_gpu.create(w * scale, (out_tile_y1 - out_tile_y0) * scale, channels, (size_t)4u, 1, blob_vkallocator);
+            in_tile_gpu.create(w, h, 3, in_out_tile_elemsize, 1, blob_vkallocator);
+            if (in_tile_gpu.empty())
+                return REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY;
+
+            std::vector<ncnn::VkMat> bindings(3);
+            bindings[0] = in_gpu;
+            bindings[1] = in_tile_gpu;
+            bindings[2] = in_alpha_tile_gpu;
+
+            preproc_constants[3].i = in_tile_gpu.w;
+            preproc_constants[4].i = in_tile_gpu.h;
+            preproc_constants[5].i = in_tile_gpu.cstep;
+
+            dispatcher.w = in_tile_gpu.w;
+            dispatcher.h = in_tile_gpu.h;
+
+            cmd.record_pipeline(realesrgan_preproc, bindings, preproc_constants, dispatcher);
         }
 
-        for (int xi = 0; xi < xtiles; xi++)
+        // realesrgan
+        ncnn::VkMat out_tile_gpu;
         {
-            const int tile_w_nopad = std::min((xi + 1) * TILE_SIZE_X, w) - xi * TILE_SIZE_X;
+            ncnn::Extractor ex = net.create_extractor();
 
-            if (tta_mode)
-            {
-                // preproc
-                ncnn::VkMat in_tile_gpu[8];
-                ncnn::VkMat in_alpha_tile_gpu;
-                {
-                    // crop tile
-                    int tile_x0 = xi * TILE_SIZE_X - prepadding;
-                    int tile_x1 = std::min((xi + 1) * TILE_SIZE_X, w) + prepadding;
-                    int tile_y0 = yi * TILE_SIZE_Y - prepadding;
-                    int tile_y1 = std::min((yi + 1) * TILE_SIZE_Y, h) + prepadding;
-
-                    in_tile_gpu[0].create(tile_x1 - tile_x0, tile_y1 - tile_y0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[1].create(tile_x1 - tile_x0, tile_y1 - tile_y0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[2].create(tile_x1 - tile_x0, tile_y1 - tile_y0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[3].create(tile_x1 - tile_x0, tile_y1 - tile_y0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[4].create(tile_y1 - tile_y0, tile_x1 - tile_x0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[5].create(tile_y1 - tile_y0, tile_x1 - tile_x0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[6].create(tile_y1 - tile_y0, tile_x1 - tile_x0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-                    in_tile_gpu[7].create(tile_y1 - tile_y0, tile_x1 - tile_x0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-
-                    if (channels == 4)
-                    {
-                        in_alpha_tile_gpu.create(tile_w_nopad, tile_h_nopad, 1, in_out_tile_elemsize, 1, blob_vkallocator);
-                    }
-
-                    std::vector<ncnn::VkMat> bindings(10);
-                    bindings[0] = in_gpu;
-                    bindings[1] = in_tile_gpu[0];
-                    bindings[2] = in_tile_gpu[1];
-                    bindings[3] = in_tile_gpu[2];
-                    bindings[4] = in_tile_gpu[3];
-                    bindings[5] = in_tile_gpu[4];
-                    bindings[6] = in_tile_gpu[5];
-                    bindings[7] = in_tile_gpu[6];
-                    bindings[8] = in_tile_gpu[7];
-                    bindings[9] = in_alpha_tile_gpu;
-
-                    std::vector<ncnn::vk_constant_type> constants(13);
-                    constants[0].i = in_gpu.w;
-                    constants[1].i = in_gpu.h;
-                    constants[2].i = in_gpu.cstep;
-                    constants[3].i = in_tile_gpu[0].w;
-                    constants[4].i = in_tile_gpu[0].h;
-                    constants[5].i = in_tile_gpu[0].cstep;
-                    constants[6].i = prepadding;
-                    constants[7].i = prepadding;
-                    constants[8].i = xi * TILE_SIZE_X;
-                    constants[9].i = std::min(yi * TILE_SIZE_Y, prepadding);
-                    constants[10].i = channels;
-                    constants[11].i = in_alpha_tile_gpu.w;
-                    constants[12].i = in_alpha_tile_gpu.h;
-
-                    ncnn::VkMat dispatcher;
-                    dispatcher.w = in_tile_gpu[0].w;
-                    dispatcher.h = in_tile_gpu[0].h;
-                    dispatcher.c = channels;
-
-                    cmd.record_pipeline(realesrgan_preproc, bindings, constants, dispatcher);
-                }
-
-                // realesrgan
-                ncnn::VkMat out_tile_gpu[8];
-                for (int ti = 0; ti < 8; ti++)
-                {
-                    ncnn::Extractor ex = net.create_extractor();
-
-                    ex.set_blob_vkallocator(blob_vkallocator);
-                    ex.set_workspace_vkallocator(blob_vkallocator);
-                    ex.set_staging_vkallocator(staging_vkallocator);
-
-                    ex.input("data", in_tile_gpu[ti]);
-
-                    ex.extract("output", out_tile_gpu[ti], cmd);
-
-                    {
-                        cmd.submit_and_wait();
-                        cmd.reset();
-                    }
-                }
-
-                ncnn::VkMat out_alpha_tile_gpu;
-                if (channels == 4)
-                {
-                    if (scale == 1)
-                    {
-                        out_alpha_tile_gpu = in_alpha_tile_gpu;
-                    }
-                    if (scale == 2)
-                    {
-                        bicubic_2x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                    if (scale == 3)
-                    {
-                        bicubic_3x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                    if (scale == 4)
-                    {
-                        bicubic_4x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                }
-
-                // postproc
-                {
-                    std::vector<ncnn::VkMat> bindings(10);
-                    bindings[0] = out_tile_gpu[0];
-                    bindings[1] = out_tile_gpu[1];
-                    bindings[2] = out_tile_gpu[2];
-                    bindings[3] = out_tile_gpu[3];
-                    bindings[4] = out_tile_gpu[4];
-                    bindings[5] = out_tile_gpu[5];
-                    bindings[6] = out_tile_gpu[6];
-                    bindings[7] = out_tile_gpu[7];
-                    bindings[8] = out_alpha_tile_gpu;
-                    bindings[9] = out_gpu;
-
-                    std::vector<ncnn::vk_constant_type> constants(13);
-                    constants[0].i = out_tile_gpu[0].w;
-                    constants[1].i = out_tile_gpu[0].h;
-                    constants[2].i = out_tile_gpu[0].cstep;
-                    constants[3].i = out_gpu.w;
-                    constants[4].i = out_gpu.h;
-                    constants[5].i = out_gpu.cstep;
-                    constants[6].i = xi * TILE_SIZE_X * scale;
-                    constants[7].i = std::min(TILE_SIZE_X * scale, out_gpu.w - xi * TILE_SIZE_X * scale);
-                    constants[8].i = prepadding * scale;
-                    constants[9].i = prepadding * scale;
-                    constants[10].i = channels;
-                    constants[11].i = out_alpha_tile_gpu.w;
-                    constants[12].i = out_alpha_tile_gpu.h;
-
-                    ncnn::VkMat dispatcher;
-                    dispatcher.w = std::min(TILE_SIZE_X * scale, out_gpu.w - xi * TILE_SIZE_X * scale);
-                    dispatcher.h = out_gpu.h;
-                    dispatcher.c = channels;
-
-                    cmd.record_pipeline(realesrgan_postproc, bindings, constants, dispatcher);
-                }
-            }
-            else
-            {
-                // preproc
-                ncnn::VkMat in_tile_gpu;
-                ncnn::VkMat in_alpha_tile_gpu;
-                {
-                    // crop tile
-                    int tile_x0 = xi * TILE_SIZE_X - prepadding;
-                    int tile_x1 = std::min((xi + 1) * TILE_SIZE_X, w) + prepadding;
-                    int tile_y0 = yi * TILE_SIZE_Y - prepadding;
-                    int tile_y1 = std::min((yi + 1) * TILE_SIZE_Y, h) + prepadding;
-
-                    in_tile_gpu.create(tile_x1 - tile_x0, tile_y1 - tile_y0, 3, in_out_tile_elemsize, 1, blob_vkallocator);
-
-                    if (channels == 4)
-                    {
-                        in_alpha_tile_gpu.create(tile_w_nopad, tile_h_nopad, 1, in_out_tile_elemsize, 1, blob_vkallocator);
-                    }
-
-                    std::vector<ncnn::VkMat> bindings(3);
-                    bindings[0] = in_gpu;
-                    bindings[1] = in_tile_gpu;
-                    bindings[2] = in_alpha_tile_gpu;
-
-                    std::vector<ncnn::vk_constant_type> constants(13);
-                    constants[0].i = in_gpu.w;
-                    constants[1].i = in_gpu.h;
-                    constants[2].i = in_gpu.cstep;
-                    constants[3].i = in_tile_gpu.w;
-                    constants[4].i = in_tile_gpu.h;
-                    constants[5].i = in_tile_gpu.cstep;
-                    constants[6].i = prepadding;
-                    constants[7].i = prepadding;
-                    constants[8].i = xi * TILE_SIZE_X;
-                    constants[9].i = std::min(yi * TILE_SIZE_Y, prepadding);
-                    constants[10].i = channels;
-                    constants[11].i = in_alpha_tile_gpu.w;
-                    constants[12].i = in_alpha_tile_gpu.h;
-
-                    ncnn::VkMat dispatcher;
-                    dispatcher.w = in_tile_gpu.w;
-                    dispatcher.h = in_tile_gpu.h;
-                    dispatcher.c = channels;
-
-                    cmd.record_pipeline(realesrgan_preproc, bindings, constants, dispatcher);
-                }
-
-                // realesrgan
-                ncnn::VkMat out_tile_gpu;
-                {
-                    ncnn::Extractor ex = net.create_extractor();
-
-                    ex.set_blob_vkallocator(blob_vkallocator);
-                    ex.set_workspace_vkallocator(blob_vkallocator);
-                    ex.set_staging_vkallocator(staging_vkallocator);
-
-                    ex.input("data", in_tile_gpu);
-
-                    ex.extract("output", out_tile_gpu, cmd);
-                }
-
-                ncnn::VkMat out_alpha_tile_gpu;
-                if (channels == 4)
-                {
-                    if (scale == 1)
-                    {
-                        out_alpha_tile_gpu = in_alpha_tile_gpu;
-                    }
-                    if (scale == 2)
-                    {
-                        bicubic_2x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                    if (scale == 3)
-                    {
-                        bicubic_3x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                    if (scale == 4)
-                    {
-                        bicubic_4x->forward(in_alpha_tile_gpu, out_alpha_tile_gpu, cmd, opt);
-                    }
-                }
-
-                // postproc
-                {
-                    std::vector<ncnn::VkMat> bindings(3);
-                    bindings[0] = out_tile_gpu;
-                    bindings[1] = out_alpha_tile_gpu;
-                    bindings[2] = out_gpu;
-
-                    std::vector<ncnn::vk_constant_type> constants(13);
-                    constants[0].i = out_tile_gpu.w;
-                    constants[1].i = out_tile_gpu.h;
-                    constants[2].i = out_tile_gpu.cstep;
-                    constants[3].i = out_gpu.w;
-                    constants[4].i = out_gpu.h;
-                    constants[5].i = out_gpu.cstep;
-                    constants[6].i = xi * TILE_SIZE_X * scale;
-                    constants[7].i = std::min(TILE_SIZE_X * scale, out_gpu.w - xi * TILE_SIZE_X * scale);
-                    constants[8].i = prepadding * scale;
-                    constants[9].i = prepadding * scale;
-                    constants[10].i = channels;
-                    constants[11].i = out_alpha_tile_gpu.w;
-                    constants[12].i = out_alpha_tile_gpu.h;
-
-                    ncnn::VkMat dispatcher;
-                    dispatcher.w = std::min(TILE_SIZE_X * scale, out_gpu.w - xi * TILE_SIZE_X * scale);
-                    dispatcher.h = out_gpu.h;
-                    dispatcher.c = channels;
-
-                    cmd.record_pipeline(realesrgan_postproc, bindings, constants, dispatcher);
-                }
-            }
+            ex.set_blob_vkallocator(blob_vkallocator);
+            ex.set_workspace_vkallocator(blob_vkallocator);
+            ex.set_staging_vkallocator(staging_vkallocator);
 
-            if (xtiles > 1)
-            {
-                cmd.submit_and_wait();
-                cmd.reset();
-            }
+            ex.input("data", in_tile_gpu);
+
+            int ret = ex.extract("output", out_tile_gpu, cmd);
+            if (ret != 0)
+                return ret == -100 ? REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY : REALESRGAN_ERROR_PROCESSING_FAILED;
         }
 
-        // download
+        ncnn::VkMat out_alpha_tile_gpu = upscale_alpha(in_alpha_tile_gpu, cmd, opt);
+
+        // postproc
         {
-            ncnn::Mat out;
+            std::vector<ncnn::VkMat> bindings(3);
+            bindings[0] = out_tile_gpu;
+            bindings[1] = out_alpha_tile_gpu;
+            bindings[2] = out_gpu;
 
-            if (opt.use_fp16_storage && opt.use_int8_storage)
-            {
-                out = ncnn::Mat(out_gpu.w, out_gpu.h, (unsigned char*)outimage.data + yi * scale * TILE_SIZE_Y * w * scale * channels, (size_t)channels, 1);
-            }
+            postproc_constants[0].i = out_tile_gpu.w;
+            postproc_constants[1].i = out_tile_gpu.h;
+            postproc_constants[2].i = out_tile_gpu.cstep;
+            postproc_constants[11].i = out_alpha_tile_gpu.w;
+            postproc_constants[12].i = out_alpha_tile_gpu.h;
+
+            dispatcher.w = out_gpu.w;
+            dispatcher.h = out_gpu.h;
+
+            cmd.record_pipeline(realesrgan_postproc, bindings, postproc_constants, dispatcher);
+        }
+    }
 
-            cmd.record_clone(out_gpu, out, opt);
+    // download
+    {
+        ncnn::Mat out;
 
-            cmd.submit_and_wait();
+        if (opt.use_fp16_storage && opt.use_int8_storage)
+        {
+            out = ncnn::Mat(out_gpu.w, out_gpu.h, (unsigned char*)outimage.data, (size_t)channels, 1);
+        }
 
-            if (!(opt.use_fp16_storage && opt.use_int8_storage))
+        cmd.record_clone(out_gpu, out, opt);
+
+        if (cmd.submit_and_wait() != 0)
+            return REALESRGAN_ERROR_DEVICE_LOST;
+
+        if (!(opt.use_fp16_storage && opt.use_int8_storage))
+        {
+            if (channels == 3)
             {
-                if (channels == 3)
-                {
-#if _WIN32
-                    out.to_pixels((unsigned char*)outimage.data + yi * scale * TILE_SIZE_Y * w * scale * channels, ncnn::Mat::PIXEL_RGB2BGR);
-#else
-                    out.to_pixels((unsigned char*)outimage.data + yi * scale * TILE_SIZE_Y * w * scale * channels, ncnn::Mat::PIXEL_RGB);
-#endif
-                }
-                if (channels == 4)
-                {
-#if _WIN32
-                    out.to_pixels((unsigned char*)outimage.data + yi * scale * TILE_SIZE_Y * w * scale * channels, ncnn::Mat::PIXEL_RGBA2BGRA);
-#else
-                    out.to_pixels((unsigned char*)outimage.data + yi * scale * TILE_SIZE_Y * w * scale * channels, ncnn::Mat::PIXEL_RGBA);
-#endif
-                }
+                out.to_pixels((unsigned char*)outimage.data, bgr ? ncnn::Mat::PIXEL_RGB2BGR : ncnn::Mat::PIXEL_RGB);
+            }
+            if (channels == 4)
+            {
+                out.to_pixels((unsigned char*)outimage.data, bgr ? ncnn::Mat::PIXEL_RGBA2BGRA : ncnn::Mat::PIXEL_RGBA);
             }
         }
     }
 
-    net.vulkan_device()->reclaim_blob_allocator(blob_vkallocator);
-    net.vulkan_device()->reclaim_staging_allocator(staging_vkallocator);
-
     return 0;
 }
+
+ncnn::VkMat RealESRGAN::upscale_alpha(const ncnn::VkMat& in_alpha_gpu, ncnn::VkCompute& cmd, const ncnn::Option& opt) const
+{
+    ncnn::VkMat out_alpha_gpu;
+    if (in_alpha_gpu.empty())
+        return out_alpha_gpu;
+
+    if (scale == 1)
+    {
+        out_alpha_gpu = in_alpha_gpu;
+    }
+    if (scale == 2)
+    {
+        bicubic_2x->forward(in_alpha_gpu, out_alpha_gpu, cmd, opt);
+    }
+    if (scale == 3)
+    {
+        bicubic_3x->forward(in_alpha_gpu, out_alpha_gpu, cmd, opt);
+    }
+    if (scale == 4)
+    {
+        bicubic_4x->forward(in_alpha_gpu, out_alpha_gpu, cmd, opt);
+    }
+
+    return out_alpha_gpu;
+}
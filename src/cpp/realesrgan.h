@@ -8,21 +8,58 @@
 #include "ncnn/gpu.h"
 #include "ncnn/layer.h"
 
+#include <string>
+
+// error codes returned by load_files and process
+#define REALESRGAN_ERROR_PROCESSING_FAILED -1
+#define REALESRGAN_ERROR_PARAM_PARSE_FAILED -2
+#define REALESRGAN_ERROR_WEIGHT_SIZE_MISMATCH -3
+#define REALESRGAN_ERROR_PIPELINE_CREATION_FAILED -4
+// a queue submission failed, which at this point means the device was lost
+#define REALESRGAN_ERROR_DEVICE_LOST -5
+// a C++ exception was caught at the C boundary, see realesrgan_get_last_exception
+#define REALESRGAN_ERROR_NATIVE_EXCEPTION -6
+#define REALESRGAN_ERROR_OUT_OF_DEVICE_MEMORY -100
+
+// precision modes accepted by the constructor
+#define REALESRGAN_PRECISION_AUTO 0
+#define REALESRGAN_PRECISION_FP32 1
+#define REALESRGAN_PRECISION_FP16_STORAGE 2
+#define REALESRGAN_PRECISION_FP16_ARITHMETIC 3
+
+// capability bits returned by realesrgan_get_gpu_features
+#define REALESRGAN_FEATURE_FP16_STORAGE 1
+#define REALESRGAN_FEATURE_FP16_ARITHMETIC 2
+#define REALESRGAN_FEATURE_INT8_STORAGE 4
+
+// layout of the pixels handed to realesrgan_process
+struct RealESRGANPixelFormat
+{
+    int channels;
+    // BGR or BGRA channel order, the output is written in the same order
+    bool bgr;
+};
+
 class RealESRGAN
 {
 public:
-    RealESRGAN(int gpuid, bool tta_mode = false);
+    RealESRGAN(int gpuid, bool tta_mode = false, int precision = REALESRGAN_PRECISION_AUTO);
     ~RealESRGAN();
 
     int load_files(FILE *param, FILE *bin);
 
-    int process(const ncnn::Mat& inimage, ncnn::Mat& outimage) const;
+    int process(const ncnn::Mat& inimage, ncnn::Mat& outimage, bool bgr = false) const;
 
 public:
     // realesrgan parameters
     int scale;
-    int tilesize;
-    int prepadding;
+
+    // name of the layer behind the last load_files error, empty if unknown
+    std::string error_layer;
+
+private:
+    // bicubic upscale of the alpha plane, empty when the input has no alpha
+    ncnn::VkMat upscale_alpha(const ncnn::VkMat& in_alpha_gpu, ncnn::VkCompute& cmd, const ncnn::Option& opt) const;
 
 private:
     ncnn::Net net;
@@ -1,27 +1,62 @@
 #include "realesrgan.h"
 
-extern "C" RealESRGAN *realesrgan_init(int gpuid, bool tta_mode, int scale, int tilesize) {
-    RealESRGAN *realesrgan = new RealESRGAN(gpuid, tta_mode);
-    if (tilesize == 0) {
-        uint32_t heap_budget = ncnn::get_gpu_device(gpuid)->get_heap_budget();
-        if (heap_budget > 1900) {
-            tilesize = 200;
-        } else if (heap_budget > 550) {
-            tilesize = 100;
-        } else if (heap_budget > 190) {
-            tilesize = 64;
-        } else {
-            tilesize = 32;
-        }
+#include <cstring>
+#include <exception>
+
+// message of the last exception caught on this thread, empty if none
+static thread_local char last_exception[512];
+
+// run an entry point without letting a C++ exception unwind into the caller
+template <typename R, typename F>
+static R guarded(R on_exception, F function) {
+    last_exception[0] = '\0';
+    try {
+        return function();
+    } catch (const std::exception &e) {
+        strncpy(last_exception, e.what(), sizeof(last_exception) - 1);
+        last_exception[sizeof(last_exception) - 1] = '\0';
+    } catch (...) {
+        strncpy(last_exception, "unknown C++ exception", sizeof(last_exception) - 1);
     }
-    realesrgan->tilesize = tilesize;
-    realesrgan->scale = scale;
-    realesrgan->prepadding = 10;
-    return realesrgan;
+    return on_exception;
+}
+
+extern "C" const char *realesrgan_get_last_exception() {
+    return last_exception;
+}
+
+extern "C" RealESRGAN *realesrgan_init(int gpuid, bool tta_mode, int scale, int precision) {
+    return guarded<RealESRGAN *>(nullptr, [&] {
+        RealESRGAN *realesrgan = new RealESRGAN(gpuid, tta_mode, precision);
+        realesrgan->scale = scale;
+        return realesrgan;
+    });
+}
+
+extern "C" uint32_t realesrgan_get_heap_budget(int gpuid) {
+    return guarded<uint32_t>(0, [&] {
+        return ncnn::get_gpu_device(gpuid)->get_heap_budget();
+    });
+}
+
+extern "C" int realesrgan_get_gpu_features(int gpuid) {
+    return guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        const ncnn::GpuInfo& info = ncnn::get_gpu_info(gpuid);
+        int features = 0;
+        if (info.support_fp16_storage())
+            features |= REALESRGAN_FEATURE_FP16_STORAGE;
+        if (info.support_fp16_arithmetic())
+            features |= REALESRGAN_FEATURE_FP16_ARITHMETIC;
+        if (info.support_int8_storage())
+            features |= REALESRGAN_FEATURE_INT8_STORAGE;
+        return features;
+    });
 }
 
 extern "C" int realesrgan_get_gpu_count() {
-    return ncnn::get_gpu_count();
+    return guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        return ncnn::get_gpu_count();
+    });
 }
 
 extern "C" int realesrgan_load_files(
@@ -29,7 +64,13 @@ extern "C" int realesrgan_load_files(
     FILE* param,
     FILE* bin
 ) {
-    return realesrgan->load_files(param, bin);
+    return guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        return realesrgan->load_files(param, bin);
+    });
+}
+
+extern "C" const char *realesrgan_get_error_layer(RealESRGAN *realesrgan) {
+    return realesrgan->error_layer.c_str();
 }
 
 extern "C" int realesrgan_process(
@@ -38,17 +79,26 @@ extern "C" int realesrgan_process(
     unsigned char *output_data,
     int width,
     int height,
-    int channels
+    RealESRGANPixelFormat format
 ) {
-    ncnn::Mat in_image_mat = ncnn::Mat(width, height, (void *)input_data, (size_t)channels, channels);
-    ncnn::Mat out_image_mat = ncnn::Mat(width * realesrgan->scale, height * realesrgan->scale, (void *)output_data, (size_t)channels, channels);
-    return realesrgan->process(in_image_mat, out_image_mat);
+    return guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        const int channels = format.channels;
+        ncnn::Mat in_image_mat = ncnn::Mat(width, height, (void *)input_data, (size_t)channels, channels);
+        ncnn::Mat out_image_mat = ncnn::Mat(width * realesrgan->scale, height * realesrgan->scale, (void *)output_data, (size_t)channels, channels);
+        return realesrgan->process(in_image_mat, out_image_mat, format.bgr);
+    });
 }
 
 extern "C" void realesrgan_free(RealESRGAN *realesrgan) {
-    delete realesrgan;
+    guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        delete realesrgan;
+        return 0;
+    });
 }
 
 extern "C" void realesrgan_destroy_gpu_instance() {
-    ncnn::destroy_gpu_instance();
-}
\ No newline at end of file
+    guarded<int>(REALESRGAN_ERROR_NATIVE_EXCEPTION, [&] {
+        ncnn::destroy_gpu_instance();
+        return 0;
+    });
+}
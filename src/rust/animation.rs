@@ -0,0 +1,126 @@
+use crate::Error;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::metadata::LoopCount;
+use image::{AnimationDecoder, Frame, ImageFormat};
+
+/// Decoded frames of an animated GIF, APNG or WebP. Every frame is already
+/// composited onto the full canvas, so disposal is baked into the pixels.
+#[derive(Clone)]
+pub struct Animation {
+    pub frames: Vec<Frame>,
+    pub loop_count: LoopCount,
+}
+
+impl Animation {
+    /// Returns `None` when the file is a still image.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Option<Self>, Error> {
+        let path = path.as_ref();
+        let open_error = |error: &dyn std::fmt::Display| Error::ImageOpenFailed(format!("{}: {error}", path.display()));
+
+        let reader = image::ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|error| open_error(&error))?;
+        let Some(format) = reader.format() else {
+            return Ok(None);
+        };
+
+        let file = BufReader::new(File::open(path).map_err(|error| open_error(&error))?);
+        let (loop_count, frames) = match format {
+            ImageFormat::Gif => {
+                let decoder = GifDecoder::new(file).map_err(|error| open_error(&error))?;
+                (decoder.loop_count(), decoder.into_frames())
+            }
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(file).map_err(|error| open_error(&error))?;
+                if !decoder.is_apng().map_err(|error| open_error(&error))? {
+                    return Ok(None);
+                }
+                let decoder = decoder.apng().map_err(|error| open_error(&error))?;
+                (decoder.loop_count(), decoder.into_frames())
+            }
+            ImageFormat::WebP => {
+                let decoder = WebPDecoder::new(file).map_err(|error| open_error(&error))?;
+                if !decoder.has_animation() {
+                    return Ok(None);
+                }
+                (decoder.loop_count(), decoder.into_frames())
+            }
+            _ => return Ok(None),
+        };
+        let frames = frames.collect_frames().map_err(|error| open_error(&error))?;
+
+        // a single-frame GIF is just a still image
+        if frames.len() < 2 {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { frames, loop_count }))
+    }
+
+    /// Writes a GIF or an APNG depending on the extension. The image crate cannot encode animated WebP.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path).map_err(|error| Error::ImageSaveFailed(format!("{}: {error}", path.display())))?;
+        self.save_with_format(path, format)
+    }
+
+    pub fn save_with_format<P: AsRef<Path>>(&self, path: P, format: ImageFormat) -> Result<(), Error> {
+        let path = path.as_ref();
+        let save_error = |error: &dyn std::fmt::Display| Error::ImageSaveFailed(format!("{}: {error}", path.display()));
+        if !matches!(format, ImageFormat::Gif | ImageFormat::Png) {
+            return Err(save_error(&format!("animated {format:?} output is not supported, use GIF or PNG")));
+        }
+        if self.frames.is_empty() {
+            return Err(save_error(&"animation has no frames"));
+        }
+
+        let file = BufWriter::new(File::create(path).map_err(|error| save_error(&error))?);
+
+        match format {
+            ImageFormat::Gif => {
+                let mut encoder = GifEncoder::new(file);
+                let repeat = match self.loop_count {
+                    LoopCount::Infinite => Repeat::Infinite,
+                    LoopCount::Finite(count) => Repeat::Finite(u16::try_from(count.get()).unwrap_or(u16::MAX)),
+                };
+                encoder.set_repeat(repeat).map_err(|error| save_error(&error))?;
+                encoder
+                    .encode_frames(self.frames.iter().cloned())
+                    .map_err(|error| save_error(&error))
+            }
+            _ => self.save_apng(file).map_err(|error| save_error(&error)),
+        }
+    }
+
+    fn save_apng(&self, file: BufWriter<File>) -> Result<(), png::EncodingError> {
+        let (width, height) = self.frames[0].buffer().dimensions();
+        let plays = match self.loop_count {
+            LoopCount::Infinite => 0,
+            LoopCount::Finite(count) => count.get(),
+        };
+
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, plays)?;
+        // frames are full canvases, so each one simply replaces the last
+        encoder.set_dispose_op(png::DisposeOp::Background)?;
+        encoder.set_blend_op(png::BlendOp::Source)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in &self.frames {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let milliseconds = (numerator as f64 / denominator.max(1) as f64).round();
+            writer.set_frame_delay(milliseconds.min(u16::MAX as f64) as u16, 1000)?;
+            writer.write_image_data(frame.buffer().as_raw())?;
+        }
+        writer.finish()
+    }
+}
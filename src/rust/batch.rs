@@ -0,0 +1,230 @@
+use crate::Animation;
+use crate::Error;
+use crate::RealEsrgan;
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+use image::{DynamicImage, ImageFormat};
+
+struct Job {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+enum Item {
+    Still(DynamicImage),
+    Animated(Animation),
+}
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub processed: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// Upscales every image below a directory, overlapping decode, GPU work and encode
+/// the way upstream's `-j load:proc:save` does. Each `RealEsrgan` instance gets its
+/// own processing thread.
+pub struct BatchProcessor {
+    instances: Vec<RealEsrgan>,
+    load_threads: usize,
+    save_threads: usize,
+    format: ImageFormat,
+    skip_existing: bool,
+}
+
+impl BatchProcessor {
+    pub fn new(realesrgan: RealEsrgan) -> Self {
+        Self {
+            instances: vec![realesrgan],
+            load_threads: 1,
+            save_threads: 2,
+            format: ImageFormat::Png,
+            skip_existing: false,
+        }
+    }
+
+    pub fn instance(mut self, realesrgan: RealEsrgan) -> Self {
+        self.instances.push(realesrgan);
+        self
+    }
+
+    pub fn load_threads(mut self, load_threads: usize) -> Self {
+        self.load_threads = load_threads.max(1);
+        self
+    }
+
+    pub fn save_threads(mut self, save_threads: usize) -> Self {
+        self.save_threads = save_threads.max(1);
+        self
+    }
+
+    pub fn format(mut self, format: ImageFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Leave inputs alone when their output already exists, so an interrupted run can be resumed.
+    pub fn skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    pub fn run<P, Q>(&mut self, input: P, output: Q) -> Result<BatchReport, Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let mut inputs = Vec::new();
+        collect_images(input.as_ref(), output.as_ref(), &mut inputs)?;
+        inputs.sort();
+
+        let extension = self.format.extensions_str().first().copied().unwrap_or("png");
+        let mut report = BatchReport::default();
+        let mut jobs = Vec::new();
+
+        for path in inputs {
+            let relative = path.strip_prefix(input.as_ref()).unwrap_or(&path);
+            let output = output.as_ref().join(relative).with_extension(extension);
+            if self.skip_existing && output.exists() {
+                report.skipped.push(path);
+            } else {
+                jobs.push(Job { input: path, output });
+            }
+        }
+
+        let jobs = Arc::new(Mutex::new(jobs.into_iter()));
+        let (loaded_sender, loaded_receiver) = mpsc::sync_channel::<(Job, Item)>(self.load_threads);
+        let (processed_sender, processed_receiver) = mpsc::sync_channel::<(Job, Item)>(self.save_threads);
+        let (report_sender, report_receiver) = mpsc::channel::<(PathBuf, Result<(), Error>)>();
+        let loaded_receiver = Arc::new(Mutex::new(loaded_receiver));
+        let processed_receiver = Arc::new(Mutex::new(processed_receiver));
+        let format = self.format;
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.load_threads {
+                let jobs = Arc::clone(&jobs);
+                let loaded_sender = loaded_sender.clone();
+                let report_sender = report_sender.clone();
+                scope.spawn(move || loop {
+                    let Some(job) = jobs.lock().unwrap().next() else { break };
+                    match load(&job.input) {
+                        Ok(item) => {
+                            if loaded_sender.send((job, item)).is_err() {
+                                break;
+                            }
+                        }
+                        Err(error) => {
+                            let _ = report_sender.send((job.input, Err(error)));
+                        }
+                    }
+                });
+            }
+
+            for realesrgan in self.instances.iter_mut() {
+                let loaded_receiver = Arc::clone(&loaded_receiver);
+                let processed_sender = processed_sender.clone();
+                let report_sender = report_sender.clone();
+                scope.spawn(move || {
+                    while let Some((job, item)) = receive(&loaded_receiver) {
+                        let result = match item {
+                            Item::Still(image) => realesrgan.process_image(&image).map(Item::Still),
+                            Item::Animated(animation) => realesrgan.process_animation(&animation).map(Item::Animated),
+                        };
+                        match result {
+                            Ok(item) => {
+                                if processed_sender.send((job, item)).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(error) => {
+                                let _ = report_sender.send((job.input, Err(error)));
+                            }
+                        }
+                    }
+                });
+            }
+
+            for _ in 0..self.save_threads {
+                let processed_receiver = Arc::clone(&processed_receiver);
+                let report_sender = report_sender.clone();
+                scope.spawn(move || {
+                    while let Some((job, item)) = receive(&processed_receiver) {
+                        let result = save(&item, &job.output, format);
+                        let _ = report_sender.send((job.input, result));
+                    }
+                });
+            }
+
+            drop(loaded_sender);
+            drop(processed_sender);
+            drop(report_sender);
+
+            for (input, result) in report_receiver {
+                match result {
+                    Ok(()) => report.processed.push(input),
+                    Err(error) => report.failed.push((input, error)),
+                }
+            }
+        });
+
+        Ok(report)
+    }
+}
+
+fn receive<T>(receiver: &Mutex<Receiver<T>>) -> Option<T> {
+    receiver.lock().unwrap().recv().ok()
+}
+
+fn collect_images(directory: &Path, exclude: &Path, images: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|error| Error::DirectoryReadFailed(format!("{}: {error}", directory.display())))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|error| Error::DirectoryReadFailed(format!("{}: {error}", directory.display())))?
+            .path();
+
+        if path.is_dir() {
+            // the output directory may live inside the input directory
+            if path != exclude {
+                collect_images(&path, exclude, images)?;
+            }
+        } else if ImageFormat::from_path(&path).is_ok_and(|format| format.reading_enabled()) {
+            images.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn load(input: &Path) -> Result<Item, Error> {
+    if let Some(animation) = Animation::open(input)? {
+        return Ok(Item::Animated(animation));
+    }
+    image::open(input)
+        .map(Item::Still)
+        .map_err(|error| Error::ImageOpenFailed(error.to_string()))
+}
+
+/// Writes next to the destination first so an interrupted run never leaves a truncated output behind.
+fn save(item: &Item, output: &Path, format: ImageFormat) -> Result<(), Error> {
+    let save_error = |error: &dyn std::fmt::Display| Error::ImageSaveFailed(format!("{}: {error}", output.display()));
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| save_error(&error))?;
+    }
+
+    let mut partial = output.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    match item {
+        Item::Still(image) => image.save_with_format(&partial, format).map_err(|error| save_error(&error))?,
+        Item::Animated(animation) => animation.save_with_format(&partial, format)?,
+    }
+    std::fs::rename(&partial, output).map_err(|error| save_error(&error))
+}
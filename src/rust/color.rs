@@ -0,0 +1,88 @@
+use crate::Error;
+use crate::realesrgan::luminance;
+
+use image::{ColorType, DynamicImage, ImageBuffer};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+/// An embedded ICC profile and the sRGB space the network was trained in.
+pub(crate) struct ColorSpace {
+    profile: ColorProfile,
+    /// sRGB, or gray on the sRGB curve when the profile is gray.
+    srgb: ColorProfile,
+}
+
+impl ColorSpace {
+    pub fn new(icc_profile: &[u8]) -> Result<Self, Error> {
+        let profile = ColorProfile::new_from_slice(icc_profile)
+            .map_err(|error| Error::InvalidColorProfile(error.to_string()))?;
+
+        let srgb = if profile.color_space == DataColorSpace::Gray {
+            let mut gray = ColorProfile::new_gray_with_gamma(2.2);
+            gray.gray_trc = ColorProfile::new_srgb().red_trc;
+            gray
+        } else {
+            ColorProfile::new_srgb()
+        };
+
+        Ok(Self { profile, srgb })
+    }
+
+    pub fn to_srgb(&self, image: &DynamicImage) -> Result<DynamicImage, Error> {
+        convert(image, &self.profile, &self.srgb)
+    }
+
+    pub fn to_profile(&self, image: &DynamicImage) -> Result<DynamicImage, Error> {
+        convert(image, &self.srgb, &self.profile)
+    }
+}
+
+/// Alpha passes through untouched and gray images stay gray: through a gray profile
+/// directly, or through RGB and back by luminance when the profile is RGB.
+fn convert(image: &DynamicImage, source: &ColorProfile, destination: &ColorProfile) -> Result<DynamicImage, Error> {
+    let color = image.color();
+    let gray = matches!(color, ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16);
+    let alpha = color.has_alpha();
+
+    let output = match (gray, alpha) {
+        (true, _) if source.color_space == DataColorSpace::Gray => match alpha {
+            true => transform(source, destination, Layout::GrayAlpha, image.to_luma_alpha8().as_raw())?,
+            false => transform(source, destination, Layout::Gray, image.to_luma8().as_raw())?,
+        },
+        (true, true) => transform(source, destination, Layout::Rgba, image.to_rgba8().as_raw())?
+            .chunks_exact(4)
+            .flat_map(|pixel| [luminance(pixel), pixel[3]])
+            .collect(),
+        (true, false) => transform(source, destination, Layout::Rgb, image.to_rgb8().as_raw())?
+            .chunks_exact(3)
+            .map(luminance)
+            .collect(),
+        (false, true) => transform(source, destination, Layout::Rgba, image.to_rgba8().as_raw())?,
+        (false, false) => transform(source, destination, Layout::Rgb, image.to_rgb8().as_raw())?,
+    };
+
+    let (width, height) = (image.width(), image.height());
+    // transforms are 8-bit like inference, 16-bit gray only gets its depth back
+    let widen = |output: Vec<u8>| output.into_iter().map(|value| value as u16 * 257).collect::<Vec<u16>>();
+    let converted = match color {
+        ColorType::L8 => ImageBuffer::from_raw(width, height, output).map(DynamicImage::ImageLuma8),
+        ColorType::La8 => ImageBuffer::from_raw(width, height, output).map(DynamicImage::ImageLumaA8),
+        ColorType::L16 => ImageBuffer::from_raw(width, height, widen(output)).map(DynamicImage::ImageLuma16),
+        ColorType::La16 => ImageBuffer::from_raw(width, height, widen(output)).map(DynamicImage::ImageLumaA16),
+        _ if alpha => ImageBuffer::from_raw(width, height, output).map(DynamicImage::ImageRgba8),
+        _ => ImageBuffer::from_raw(width, height, output).map(DynamicImage::ImageRgb8),
+    };
+
+    converted.ok_or(Error::ColorConversionFailed)
+}
+
+fn transform(source: &ColorProfile, destination: &ColorProfile, layout: Layout, input: &[u8]) -> Result<Vec<u8>, Error> {
+    let transform_error = |error: moxcms::CmsError| Error::InvalidColorProfile(error.to_string());
+
+    let mut output = vec![0u8; input.len()];
+    source
+        .create_transform_8bit(layout, destination, layout, TransformOptions::default())
+        .map_err(transform_error)?
+        .transform(input, &mut output)
+        .map_err(transform_error)?;
+    Ok(output)
+}
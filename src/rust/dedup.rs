@@ -0,0 +1,64 @@
+use crate::Error;
+
+/// Mean absolute difference between two equally sized buffers, from 0 (identical) to 255.
+pub fn frame_difference(a: &[u8], b: &[u8]) -> f32 {
+    if a.len() != b.len() {
+        return f32::MAX;
+    }
+    if a.is_empty() {
+        return 0.0;
+    }
+
+    let total: u64 = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+    total as f32 / a.len() as f32
+}
+
+/// Reuses the last upscaled output for consecutive frames that barely differ from
+/// the frame it was made from. Comparing against that frame rather than the
+/// previous one keeps slow fades from drifting through a run of skips.
+pub struct FrameDeduplicator<T> {
+    threshold: Option<f32>,
+    last: Option<(Vec<u8>, T)>,
+    skipped: u64,
+}
+
+impl<T> FrameDeduplicator<T> {
+    /// A `threshold` of 0 only skips exact repeats, `None` never skips.
+    pub fn new(threshold: Option<f32>) -> Self {
+        Self {
+            threshold,
+            last: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the output for `frame` and whether it was reused.
+    pub fn process<F>(&mut self, frame: &[u8], upscale: F) -> Result<(&T, bool), Error>
+    where
+        F: FnOnce(&[u8]) -> Result<T, Error>,
+    {
+        let duplicate = match (self.threshold, self.last.as_ref()) {
+            (Some(threshold), Some((source, _))) => frame_difference(source, frame) <= threshold,
+            _ => false,
+        };
+
+        if duplicate {
+            self.skipped += 1;
+        } else {
+            let output = upscale(frame)?;
+            let mut source = self.last.take().map(|(source, _)| source).unwrap_or_default();
+            source.clear();
+            if self.threshold.is_some() {
+                source.extend_from_slice(frame);
+            }
+            self.last = Some((source, output));
+        }
+
+        let (_, output) = self.last.as_ref().expect("output was just stored");
+        Ok((output, duplicate))
+    }
+}
@@ -1,3 +1,5 @@
+use crate::OptionsPrecision;
+
 use thiserror::Error;
 
 #[derive(Debug, Error, Clone)]
@@ -5,15 +7,36 @@ pub enum Error {
     #[error("GPU {requested} not found. Available GPUs: {available}")]
     GpuNotFound { requested: i32, available: i32 },
 
+    #[error("GPU {gpuid} does not support {precision:?} precision.")]
+    UnsupportedPrecision { gpuid: i32, precision: OptionsPrecision },
+
     #[error("Invalid model provided. Both parameter and binary data must be non-empty.")]
     InvalidModel,
 
+    #[error("The paired models cannot be interpolated: {0}")]
+    IncompatibleModels(String),
+
     #[error("Failed to create in-memory file pointers for model data.")]
     FilePointerCreationFailed,
 
     #[error("Failed to load model files. Error code: {code}")]
     ModelLoadFailed { code: i32 },
 
+    #[error("Failed to parse the model param file.")]
+    ParamParseFailed,
+
+    #[error("Model weights do not match the param file (layer: {}).", .layer.as_deref().unwrap_or("unknown"))]
+    WeightSizeMismatch { layer: Option<String> },
+
+    #[error("Failed to create a GPU pipeline (layer: {}).", .layer.as_deref().unwrap_or("unknown"))]
+    PipelineCreationFailed { layer: Option<String> },
+
+    #[error("The GPU device was lost while processing.")]
+    DeviceLost,
+
+    #[error("The native library threw an exception: {0}")]
+    NativeException(String),
+
     #[error("The instance pointer is null. It may have been dropped or failed to initialize.")]
     InvalidPointer,
 
@@ -23,14 +46,53 @@ pub enum Error {
     #[error("Invalid input dimensions: expected byte length to be a multiple of {expected_length}, but got {actual_length}.")]
     InvalidInput { expected_length: usize, actual_length: usize },
 
+    #[error("Tile backend returned {actual_length} bytes, expected {expected_length}.")]
+    InvalidTileOutput { expected_length: usize, actual_length: usize },
+
+    #[error("Ran out of GPU memory with {tile_width}x{tile_height} tiles. Try a smaller tile size.")]
+    OutOfDeviceMemory { tile_width: i32, tile_height: i32 },
+
     #[error("Failed to process the image. Error code: {code}")]
     ProcessingFailed { code: i32 },
 
+    #[error("Invalid pipeline: {0}")]
+    InvalidPipeline(String),
+
+    #[error("Invalid Y4M stream: {0}")]
+    Y4mParseFailed(String),
+
+    #[error("Failed to read or write Y4M stream: {0}")]
+    Y4mIoFailed(String),
+
     #[cfg(feature = "image")]
     #[error("Failed to open image file: {0}")]
     ImageOpenFailed(String),
 
+    #[cfg(feature = "image")]
+    #[error("Failed to save image file: {0}")]
+    ImageSaveFailed(String),
+
+    #[cfg(feature = "image")]
+    #[error("Failed to read directory: {0}")]
+    DirectoryReadFailed(String),
+
     #[cfg(feature = "image")]
     #[error("Failed to convert the processed image buffer to the target color type.")]
     ColorConversionFailed,
+
+    #[cfg(feature = "color-management")]
+    #[error("Failed to convert between the embedded ICC profile and sRGB: {0}")]
+    InvalidColorProfile(String),
+
+    #[cfg(feature = "async")]
+    #[error("The upscaling thread panicked.")]
+    WorkerPanicked,
+
+    #[cfg(feature = "video")]
+    #[error("Failed to probe video: {0}")]
+    VideoProbeFailed(String),
+
+    #[cfg(feature = "video")]
+    #[error("ffmpeg failed: {0}")]
+    FfmpegFailed(String),
 }
\ No newline at end of file
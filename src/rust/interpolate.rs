@@ -0,0 +1,213 @@
+use crate::Error;
+
+const TAG_FP16: u32 = 0x0130_6B47;
+const TAG_INT8: u32 = 0x000D_4B38;
+const TAG_FP32_SCALED: u32 = 0x0002_C056;
+
+/// How a blob is stored in an ncnn `.bin`: behind a storage tag, or as plain fp32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Blob {
+    Tagged(usize),
+    Raw(usize),
+}
+
+/// Blends the weights of two models sharing one param file, `strength` of `strong` and the rest of `weak`,
+/// like the denoise strength of `realesr-general-x4v3` and `realesr-general-wdn-x4v3`.
+pub(crate) fn interpolate_weights(
+    (param, strong): (&[u8], &[u8]),
+    (weak_param, weak): (&[u8], &[u8]),
+    strength: f32,
+) -> Result<Vec<u8>, Error> {
+    if param != weak_param {
+        return Err(incompatible("the param files differ".to_string()));
+    }
+    if strong.len() != weak.len() {
+        return Err(incompatible(format!("the weights are {} and {} bytes", strong.len(), weak.len())));
+    }
+
+    let mut output = Vec::with_capacity(strong.len());
+    let mut offset = 0;
+    for blob in blobs(param)? {
+        let (tag, count) = match blob {
+            Blob::Raw(count) => (0, count),
+            Blob::Tagged(count) => {
+                let tag = read_u32(strong, offset)?;
+                if tag != read_u32(weak, offset)? {
+                    return Err(incompatible(format!("the weights are stored differently at byte {offset}")));
+                }
+                output.extend_from_slice(&strong[offset..offset + 4]);
+                offset += 4;
+                (tag, count)
+            }
+        };
+
+        match tag {
+            TAG_FP16 => {
+                let end = offset + (count * 2).next_multiple_of(4);
+                check_length(strong, end)?;
+                for index in 0..count {
+                    let at = offset + index * 2;
+                    let strong = f16_to_f32(u16::from_le_bytes([strong[at], strong[at + 1]]));
+                    let weak = f16_to_f32(u16::from_le_bytes([weak[at], weak[at + 1]]));
+                    output.extend_from_slice(&f32_to_f16(lerp(weak, strong, strength)).to_le_bytes());
+                }
+                output.resize(output.len() + end - offset - count * 2, 0);
+                offset = end;
+            }
+            TAG_INT8 => return Err(incompatible("int8 weights cannot be interpolated".to_string())),
+            TAG_FP32_SCALED | 0 => {
+                let end = offset + count * 4;
+                check_length(strong, end)?;
+                for at in (offset..end).step_by(4) {
+                    let strong = f32::from_le_bytes(strong[at..at + 4].try_into().unwrap());
+                    let weak = f32::from_le_bytes(weak[at..at + 4].try_into().unwrap());
+                    output.extend_from_slice(&lerp(weak, strong, strength).to_le_bytes());
+                }
+                offset = end;
+            }
+            _ => return Err(incompatible("quantized weights cannot be interpolated".to_string())),
+        }
+    }
+
+    if offset != strong.len() {
+        return Err(incompatible(format!("the param file describes {offset} of {} bytes", strong.len())));
+    }
+    Ok(output)
+}
+
+/// The weight blobs of every layer in a text param file, in the order ncnn loads them.
+fn blobs(param: &[u8]) -> Result<Vec<Blob>, Error> {
+    let param = std::str::from_utf8(param).map_err(|_| incompatible("only text param files are supported".to_string()))?;
+    let mut lines = param.lines().filter(|line| !line.trim().is_empty());
+    if lines.next().map(str::trim) != Some("7767517") {
+        return Err(incompatible("the param file has no ncnn magic number".to_string()));
+    }
+    lines.next();
+
+    let mut blobs = Vec::new();
+    for line in lines {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().unwrap_or_default();
+        let name = fields.next().unwrap_or_default();
+        let inputs: usize = fields.next().and_then(|count| count.parse().ok()).unwrap_or(0);
+        let outputs: usize = fields.next().and_then(|count| count.parse().ok()).unwrap_or(0);
+        let values: Vec<(i32, &str)> = fields
+            .skip(inputs + outputs)
+            .filter_map(|field| field.split_once('='))
+            .filter_map(|(id, value)| Some((id.parse().ok()?, value)))
+            .collect();
+        let value = |id: i32| -> usize {
+            values
+                .iter()
+                .find(|(key, _)| *key == id)
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(0)
+        };
+
+        match kind {
+            "Convolution" | "ConvolutionDepthWise" | "Deconvolution" | "DeconvolutionDepthWise" => {
+                if value(8) != 0 {
+                    return Err(incompatible(format!("layer {name} is int8 quantized")));
+                }
+                if value(19) != 0 {
+                    continue;
+                }
+                blobs.push(Blob::Tagged(value(6)));
+                if value(5) != 0 {
+                    blobs.push(Blob::Raw(value(0)));
+                }
+            }
+            "InnerProduct" => {
+                if value(8) != 0 {
+                    return Err(incompatible(format!("layer {name} is int8 quantized")));
+                }
+                blobs.push(Blob::Tagged(value(2)));
+                if value(1) != 0 {
+                    blobs.push(Blob::Raw(value(0)));
+                }
+            }
+            "PReLU" => blobs.push(Blob::Raw(value(0))),
+            "BatchNorm" | "Scale" | "InstanceNorm" | "LayerNorm" | "GroupNorm" | "MemoryData" | "Embed" | "LSTM" | "GRU"
+            | "RNN" | "MultiHeadAttention" => {
+                return Err(incompatible(format!("layer {name} of type {kind} is not supported")));
+            }
+            _ => {}
+        }
+    }
+    Ok(blobs)
+}
+
+fn lerp(weak: f32, strong: f32, strength: f32) -> f32 {
+    weak + (strong - weak) * strength
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    check_length(bytes, offset + 4)?;
+    Ok(u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()))
+}
+
+fn check_length(bytes: &[u8], end: usize) -> Result<(), Error> {
+    if end > bytes.len() {
+        return Err(incompatible(format!("the weights end at byte {} before {end}", bytes.len())));
+    }
+    Ok(())
+}
+
+fn incompatible(reason: String) -> Error {
+    Error::IncompatibleModels(reason)
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = (half as u32 & 0x8000) << 16;
+    let exponent = (half >> 10) & 0x1F;
+    let mantissa = half as u32 & 0x03FF;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal, renormalized for fp32
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x03FF) << 13
+        }
+        0x1F => sign | 0x7F80_0000 | mantissa << 13,
+        exponent => sign | ((exponent as u32 + 112) << 23) | mantissa << 13,
+    };
+    f32::from_bits(bits)
+}
+
+/// Rounds to the nearest fp16, ties to even.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+
+    let exponent = exponent - 112;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    let (mantissa, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        (mantissa | 0x0080_0000, (14 - exponent) as u32)
+    } else {
+        (mantissa, 13)
+    };
+
+    let half = mantissa >> shift;
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if remainder > halfway || (remainder == halfway && half & 1 == 1) { half + 1 } else { half };
+
+    // a carry out of the mantissa rolls over into the exponent, up to infinity
+    let exponent = exponent.max(0) as u32;
+    sign | ((exponent << 10) + rounded) as u16
+}
+
+#[cfg(test)]
+mod tests;
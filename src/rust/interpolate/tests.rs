@@ -0,0 +1,80 @@
+use super::*;
+
+const PARAM: &[u8] = b"7767517
+4 4
+Input            data     0 1 data
+Convolution      conv_0   1 1 data conv_0 0=2 1=1 5=1 6=4
+PReLU            prelu_0  1 1 conv_0 prelu_0 0=2
+Convolution      conv_1   1 1 prelu_0 output 0=1 1=1 5=0 6=3
+";
+
+fn fp32(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn fp16(values: &[f32]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = values.iter().flat_map(|value| f32_to_f16(*value).to_le_bytes()).collect();
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+    bytes
+}
+
+/// conv_0 in fp32, conv_1 in fp16, each behind its storage tag.
+fn weights(conv_0: [f32; 4], bias: [f32; 2], slope: [f32; 2], conv_1: [f32; 3]) -> Vec<u8> {
+    let mut bin = 0u32.to_le_bytes().to_vec();
+    bin.extend(fp32(&conv_0));
+    bin.extend(fp32(&bias));
+    bin.extend(fp32(&slope));
+    bin.extend(TAG_FP16.to_le_bytes());
+    bin.extend(fp16(&conv_1));
+    bin
+}
+
+#[test]
+fn interpolates_every_blob() {
+    let strong = weights([1.0, 2.0, 3.0, 4.0], [0.5, -0.5], [0.25, 0.25], [1.0, -2.0, 8.0]);
+    let weak = weights([3.0, 2.0, 1.0, 0.0], [1.5, 0.5], [0.75, 0.25], [3.0, 2.0, 0.0]);
+
+    let output = interpolate_weights((PARAM, &strong), (PARAM, &weak), 0.75).unwrap();
+    assert_eq!(output, weights([1.5, 2.0, 2.5, 3.0], [0.75, -0.25], [0.375, 0.25], [1.5, -1.0, 6.0]));
+}
+
+#[test]
+fn ends_keep_their_model() {
+    let strong = weights([1.0, 2.0, 3.0, 4.0], [0.5, -0.5], [0.25, 0.25], [0.1, -0.2, 0.3]);
+    let weak = weights([3.0, 2.0, 1.0, 0.0], [1.5, 0.5], [0.75, 0.25], [3.0, 2.0, 0.0]);
+
+    assert_eq!(interpolate_weights((PARAM, &strong), (PARAM, &weak), 1.0).unwrap(), strong);
+    assert_eq!(interpolate_weights((PARAM, &strong), (PARAM, &weak), 0.0).unwrap(), weak);
+}
+
+#[test]
+fn rejects_mismatched_models() {
+    let strong = weights([0.0; 4], [0.0; 2], [0.0; 2], [0.0; 3]);
+
+    let other_param = b"7767517\n1 1\nInput data 0 1 data\n";
+    assert!(matches!(interpolate_weights((PARAM, &strong), (other_param, &strong), 0.5), Err(Error::IncompatibleModels(_))));
+    assert!(matches!(
+        interpolate_weights((PARAM, &strong), (PARAM, &strong[..strong.len() - 4]), 0.5),
+        Err(Error::IncompatibleModels(_))
+    ));
+    assert!(matches!(interpolate_weights((PARAM, &strong[..20]), (PARAM, &strong[..20]), 0.5), Err(Error::IncompatibleModels(_))));
+
+    let mut int8 = strong.clone();
+    int8[..4].copy_from_slice(&TAG_INT8.to_le_bytes());
+    assert!(matches!(interpolate_weights((PARAM, &int8), (PARAM, &int8), 0.5), Err(Error::IncompatibleModels(_))));
+}
+
+#[test]
+fn half_floats_round_trip() {
+    for value in [0.0f32, -0.0, 1.0, -2.5, 65504.0, 6.1035156e-5, 5.9604645e-8, 3.0517578e-5] {
+        assert_eq!(f16_to_f32(f32_to_f16(value)), value);
+    }
+    assert_eq!(f32_to_f16(1.0 + 1.0 / 4096.0), 0x3C00);
+    assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3C02);
+    assert_eq!(f32_to_f16(70000.0), 0x7C00);
+    assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+    for half in (0..=u16::MAX).filter(|half| half & 0x7C00 != 0x7C00 || half & 0x03FF == 0) {
+        assert_eq!(f32_to_f16(f16_to_f32(half)), half, "{half:#06x}");
+    }
+}
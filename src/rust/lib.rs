@@ -1,14 +1,63 @@
 mod options;
 mod realesrgan;
+mod tiler;
+mod tta;
+mod resample;
+mod region;
+mod pipeline;
+mod view;
+mod stream;
+mod yuv;
+mod dedup;
+mod interpolate;
+mod y4m;
 mod error;
+#[cfg(feature = "image")]
+mod batch;
+#[cfg(feature = "image")]
+mod animation;
+#[cfg(feature = "image")]
+mod metadata;
+#[cfg(feature = "color-management")]
+mod color;
+#[cfg(feature = "video")]
+mod video;
 
 pub use options::Options;
 pub use options::OptionsScaleFactor;
+pub use options::OptionsTileBlend;
+pub use options::OptionsPrecision;
+pub use options::OptionsResampleFilter;
+pub use options::OptionsRestoreMode;
+pub use options::OptionsAlphaMode;
+pub use options::{OptionsTtaMerge, OptionsTtaMode};
 pub use error::Error;
 pub use realesrgan::RealEsrgan;
+pub use tiler::{Tile, TileBackend, Tiler};
+pub use tta::Tta;
+pub use resample::Resampler;
+pub use region::{enhance_regions, Region, RegionEnhancer};
+pub use pipeline::Pipeline;
+pub use view::{ImageView, PixelFormat};
+pub use stream::ProcessIter;
+pub use yuv::{ChromaSampling, ColorMatrix, ColorRange, YuvFrame};
+pub use y4m::{Y4mHeader, Y4mReader, Y4mWriter};
+pub use dedup::{frame_difference, FrameDeduplicator};
+
+#[cfg(feature = "async")]
+pub use stream::ProcessStream;
 
 #[cfg(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime"))]
 pub use options::OptionsModel;
 
+#[cfg(feature = "image")]
+pub use batch::{BatchProcessor, BatchReport};
+
+#[cfg(feature = "image")]
+pub use animation::Animation;
+
+#[cfg(feature = "video")]
+pub use video::{VideoInfo, VideoProgress, VideoReport, VideoUpscaler};
+
 #[cfg(feature = "image")]
 pub use image::DynamicImage as Image;
@@ -0,0 +1,337 @@
+use crate::Error;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+use image::codecs::jpeg::{JpegEncoder, PixelDensity, PixelDensityUnit};
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+
+const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const JPEG_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const CENTIMETERS_PER_INCH: f64 = 2.54;
+const METERS_PER_INCH: f64 = 0.0254;
+
+const TAG_X_RESOLUTION: u16 = 0x011a;
+const TAG_Y_RESOLUTION: u16 = 0x011b;
+const TAG_RESOLUTION_UNIT: u16 = 0x0128;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_PIXEL_X_DIMENSION: u16 = 0xa002;
+const TAG_PIXEL_Y_DIMENSION: u16 = 0xa003;
+
+/// What `process_file_to` carries from the input file to its output.
+#[derive(Debug, Default)]
+pub(crate) struct Metadata {
+    pub icc_profile: Option<Vec<u8>>,
+    /// A TIFF-structured EXIF block with the orientation already cleared.
+    pub exif: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+    /// Horizontal and vertical dots per inch.
+    pub dpi: Option<(f64, f64)>,
+}
+
+impl Metadata {
+    /// Adjusts for an image `scale` times larger, now `width`x`height`, so the physical size stays the same.
+    pub fn scale(&mut self, scale: u32, width: u32, height: u32) {
+        self.dpi = self.dpi.map(|(x, y)| (x * scale as f64, y * scale as f64));
+        if let Some(exif) = self.exif.as_mut() {
+            scale_exif(exif, scale, width, height);
+        }
+    }
+}
+
+/// Decodes `path` with EXIF orientation applied.
+pub(crate) fn open(path: &Path) -> Result<(DynamicImage, Metadata), Error> {
+    let open_error = |error: &dyn std::fmt::Display| Error::ImageOpenFailed(format!("{}: {error}", path.display()));
+
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|error| open_error(&error))?;
+    let format = reader.format();
+    let mut decoder = reader.into_decoder().map_err(|error| open_error(&error))?;
+
+    let icc_profile = decoder.icc_profile().map_err(|error| open_error(&error))?;
+    let mut exif = decoder.exif_metadata().map_err(|error| open_error(&error))?;
+    let xmp = decoder.xmp_metadata().map_err(|error| open_error(&error))?;
+    let orientation = decoder.orientation().map_err(|error| open_error(&error))?;
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|error| open_error(&error))?;
+    image.apply_orientation(orientation);
+    if let Some(exif) = exif.as_mut() {
+        let _ = Orientation::remove_from_exif_chunk(exif);
+    }
+
+    let dpi = match format {
+        Some(ImageFormat::Png) => png_dpi(path),
+        Some(ImageFormat::Jpeg) => jfif_dpi(path),
+        _ => None,
+    }
+    .or_else(|| exif.as_deref().and_then(exif_dpi));
+
+    Ok((image, Metadata { icc_profile, exif, xmp, dpi }))
+}
+
+/// Encodes with as much of `metadata` as the format can hold. PNG and JPEG keep
+/// everything, WebP keeps the ICC profile and EXIF, other formats keep nothing.
+pub(crate) fn save(image: &DynamicImage, metadata: &Metadata, path: &Path, format: ImageFormat) -> Result<(), Error> {
+    let save_error = |error: &dyn std::fmt::Display| Error::ImageSaveFailed(format!("{}: {error}", path.display()));
+
+    match format {
+        ImageFormat::Png => save_png(image, metadata, path).map_err(|error| save_error(&error)),
+        ImageFormat::Jpeg => {
+            let mut encoded = Vec::new();
+            let mut encoder = JpegEncoder::new(&mut encoded);
+            if let Some((x, y)) = metadata.dpi {
+                encoder.set_pixel_density(PixelDensity {
+                    density: (x.round().min(u16::MAX as f64) as u16, y.round().min(u16::MAX as f64) as u16),
+                    unit: PixelDensityUnit::Inches,
+                });
+            }
+            set_metadata(&mut encoder, metadata).map_err(|error| save_error(&error))?;
+
+            // JPEG has no alpha channel
+            let opaque;
+            let image = if image.color().has_alpha() {
+                opaque = DynamicImage::ImageRgb8(image.to_rgb8());
+                &opaque
+            } else {
+                image
+            };
+            image.write_with_encoder(encoder).map_err(|error| save_error(&error))?;
+
+            if let Some(xmp) = &metadata.xmp {
+                encoded = insert_jpeg_xmp(encoded, xmp);
+            }
+            std::fs::write(path, encoded).map_err(|error| save_error(&error))
+        }
+        ImageFormat::WebP => {
+            let file = BufWriter::new(File::create(path).map_err(|error| save_error(&error))?);
+            let mut encoder = WebPEncoder::new_lossless(file);
+            set_metadata(&mut encoder, metadata).map_err(|error| save_error(&error))?;
+            image.write_with_encoder(encoder).map_err(|error| save_error(&error))
+        }
+        format => image.save_with_format(path, format).map_err(|error| save_error(&error)),
+    }
+}
+
+fn set_metadata<E: ImageEncoder>(encoder: &mut E, metadata: &Metadata) -> Result<(), image::error::UnsupportedError> {
+    if let Some(icc_profile) = &metadata.icc_profile {
+        encoder.set_icc_profile(icc_profile.clone())?;
+    }
+    if let Some(exif) = &metadata.exif {
+        encoder.set_exif_metadata(exif.clone())?;
+    }
+    Ok(())
+}
+
+/// The image crate's PNG encoder cannot write XMP or pHYs, so this goes through `png` directly.
+fn save_png(image: &DynamicImage, metadata: &Metadata, path: &Path) -> Result<(), png::EncodingError> {
+    let converted;
+    let wide;
+    let (color, depth, data) = match image {
+        DynamicImage::ImageRgb8(buffer) => (png::ColorType::Rgb, png::BitDepth::Eight, buffer.as_raw()),
+        DynamicImage::ImageRgba8(buffer) => (png::ColorType::Rgba, png::BitDepth::Eight, buffer.as_raw()),
+        DynamicImage::ImageLuma8(buffer) => (png::ColorType::Grayscale, png::BitDepth::Eight, buffer.as_raw()),
+        DynamicImage::ImageLumaA8(buffer) => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, buffer.as_raw()),
+        // PNG samples are big-endian
+        DynamicImage::ImageLuma16(buffer) => {
+            wide = buffer.as_raw().iter().flat_map(|sample| sample.to_be_bytes()).collect::<Vec<u8>>();
+            (png::ColorType::Grayscale, png::BitDepth::Sixteen, &wide)
+        }
+        DynamicImage::ImageLumaA16(buffer) => {
+            wide = buffer.as_raw().iter().flat_map(|sample| sample.to_be_bytes()).collect::<Vec<u8>>();
+            (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen, &wide)
+        }
+        _ => {
+            converted = image.to_rgba8();
+            (png::ColorType::Rgba, png::BitDepth::Eight, converted.as_raw())
+        }
+    };
+
+    let mut info = png::Info::with_size(image.width(), image.height());
+    info.color_type = color;
+    info.bit_depth = depth;
+    info.icc_profile = metadata.icc_profile.as_deref().map(Into::into);
+    info.exif_metadata = metadata.exif.as_deref().map(Into::into);
+    info.pixel_dims = metadata.dpi.map(|(x, y)| png::PixelDimensions {
+        xppu: (x / METERS_PER_INCH).round() as u32,
+        yppu: (y / METERS_PER_INCH).round() as u32,
+        unit: png::Unit::Meter,
+    });
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::with_info(file, info)?;
+    if let Some(xmp) = &metadata.xmp {
+        encoder.add_itxt_chunk(XMP_KEYWORD.to_string(), String::from_utf8_lossy(xmp).into_owned())?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()
+}
+
+fn png_dpi(path: &Path) -> Option<(f64, f64)> {
+    let file = BufReader::new(File::open(path).ok()?);
+    let reader = png::Decoder::new(file).read_info().ok()?;
+    let dims = reader.info().pixel_dims?;
+    match dims.unit {
+        png::Unit::Meter => Some((dims.xppu as f64 * METERS_PER_INCH, dims.yppu as f64 * METERS_PER_INCH)),
+        png::Unit::Unspecified => None,
+    }
+}
+
+/// Reads the density from a JFIF APP0 segment, which always directly follows SOI.
+fn jfif_dpi(path: &Path) -> Option<(f64, f64)> {
+    let mut header = [0u8; 18];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if header[..4] != [0xff, 0xd8, 0xff, 0xe0] || &header[6..11] != b"JFIF\0" {
+        return None;
+    }
+
+    let x = u16::from_be_bytes([header[14], header[15]]) as f64;
+    let y = u16::from_be_bytes([header[16], header[17]]) as f64;
+    match header[13] {
+        1 => Some((x, y)),
+        2 => Some((x * CENTIMETERS_PER_INCH, y * CENTIMETERS_PER_INCH)),
+        _ => None,
+    }
+}
+
+/// Places an APP1 XMP segment after SOI and any JFIF/EXIF APP segments the encoder wrote.
+fn insert_jpeg_xmp(jpeg: Vec<u8>, xmp: &[u8]) -> Vec<u8> {
+    let length = JPEG_XMP_SIGNATURE.len() + xmp.len() + 2;
+    if length > u16::MAX as usize || jpeg.len() < 2 {
+        return jpeg;
+    }
+
+    let mut position = 2;
+    while position + 4 <= jpeg.len() && jpeg[position] == 0xff && matches!(jpeg[position + 1], 0xe0 | 0xe1) {
+        position += 2 + u16::from_be_bytes([jpeg[position + 2], jpeg[position + 3]]) as usize;
+    }
+    let position = position.min(jpeg.len());
+
+    let mut output = Vec::with_capacity(jpeg.len() + length + 2);
+    output.extend_from_slice(&jpeg[..position]);
+    output.extend_from_slice(&[0xff, 0xe1]);
+    output.extend_from_slice(&(length as u16).to_be_bytes());
+    output.extend_from_slice(JPEG_XMP_SIGNATURE);
+    output.extend_from_slice(xmp);
+    output.extend_from_slice(&jpeg[position..]);
+    output
+}
+
+struct Tiff<'a> {
+    data: &'a mut [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a mut [u8]) -> Option<Self> {
+        let big_endian = match data.get(..4)? {
+            [0x49, 0x49, 42, 0] => false,
+            [0x4d, 0x4d, 0, 42] => true,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.data.get(offset)?, *self.data.get(offset + 1)?];
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn set_u16(&mut self, offset: usize, value: u16) -> Option<()> {
+        let bytes = if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        self.data.get_mut(offset..offset + 2)?.copy_from_slice(&bytes);
+        Some(())
+    }
+
+    fn set_u32(&mut self, offset: usize, value: u32) -> Option<()> {
+        let bytes = if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        self.data.get_mut(offset..offset + 4)?.copy_from_slice(&bytes);
+        Some(())
+    }
+
+    /// Offsets of the 12-byte entries in the IFD at `offset`.
+    fn entries(&self, offset: usize) -> Vec<usize> {
+        let count = self.u16(offset).unwrap_or(0) as usize;
+        (0..count)
+            .map(|index| offset + 2 + index * 12)
+            .take_while(|entry| entry + 12 <= self.data.len())
+            .collect()
+    }
+
+    fn find(&self, ifd: usize, tag: u16) -> Option<usize> {
+        self.entries(ifd).into_iter().find(|entry| self.u16(*entry) == Some(tag))
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    fn rational(&self, entry: usize) -> Option<f64> {
+        let offset = self.u32(entry + 8)? as usize;
+        let (numerator, denominator) = (self.u32(offset)?, self.u32(offset + 4)?);
+        (denominator != 0).then(|| numerator as f64 / denominator as f64)
+    }
+
+    fn scale_rational(&mut self, entry: usize, scale: u32) -> Option<()> {
+        let offset = self.u32(entry + 8)? as usize;
+        let (numerator, denominator) = (self.u32(offset)?, self.u32(offset + 4)?);
+        match numerator.checked_mul(scale) {
+            Some(numerator) => self.set_u32(offset, numerator),
+            None if denominator % scale == 0 => self.set_u32(offset + 4, denominator / scale),
+            None => Some(()),
+        }
+    }
+
+    /// Rewrites an inline SHORT or LONG value.
+    fn set_dimension(&mut self, entry: usize, value: u32) -> Option<()> {
+        match self.u16(entry + 2)? {
+            3 => self.set_u16(entry + 8, u16::try_from(value).ok()?),
+            4 => self.set_u32(entry + 8, value),
+            _ => None,
+        }
+    }
+}
+
+fn exif_dpi(exif: &[u8]) -> Option<(f64, f64)> {
+    let mut exif = exif.to_vec();
+    let tiff = Tiff::new(&mut exif)?;
+    let ifd = tiff.first_ifd()?;
+
+    let x = tiff.rational(tiff.find(ifd, TAG_X_RESOLUTION)?)?;
+    let y = tiff.rational(tiff.find(ifd, TAG_Y_RESOLUTION)?)?;
+    let unit = tiff.find(ifd, TAG_RESOLUTION_UNIT).and_then(|entry| tiff.u16(entry + 8)).unwrap_or(2);
+    match unit {
+        2 => Some((x, y)),
+        3 => Some((x * CENTIMETERS_PER_INCH, y * CENTIMETERS_PER_INCH)),
+        _ => None,
+    }
+}
+
+/// Patches the resolution and pixel dimension tags in place, leaving the rest of the block untouched.
+fn scale_exif(exif: &mut [u8], scale: u32, width: u32, height: u32) {
+    let Some(mut tiff) = Tiff::new(exif) else { return };
+    let Some(ifd) = tiff.first_ifd() else { return };
+
+    for tag in [TAG_X_RESOLUTION, TAG_Y_RESOLUTION] {
+        if let Some(entry) = tiff.find(ifd, tag) {
+            let _ = tiff.scale_rational(entry, scale);
+        }
+    }
+
+    let Some(exif_ifd) = tiff.find(ifd, TAG_EXIF_IFD).and_then(|entry| tiff.u32(entry + 8)) else { return };
+    for (tag, value) in [(TAG_PIXEL_X_DIMENSION, width), (TAG_PIXEL_Y_DIMENSION, height)] {
+        if let Some(entry) = tiff.find(exif_ifd as usize, tag) {
+            let _ = tiff.set_dimension(entry, value);
+        }
+    }
+}
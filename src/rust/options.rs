@@ -31,6 +31,7 @@ const MODEL_REALESRGAN_X4PLUS_ANIME: (&[u8], &[u8]) = (
 );
 
 #[cfg(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OptionsModel {
     #[cfg(feature = "model-realesr-animevideov3")]
     RealESRAnimeVideoV3x2,
@@ -47,6 +48,46 @@ pub enum OptionsModel {
 #[cfg(any(feature = "model-realesr-animevideov3", feature = "model-realesrgan-plus", feature = "model-realesrgan-plus-anime"))]
 impl OptionsModel {
 
+    pub const ALL: &'static [Self] = &[
+        #[cfg(feature = "model-realesr-animevideov3")]
+        Self::RealESRAnimeVideoV3x2,
+        #[cfg(feature = "model-realesr-animevideov3")]
+        Self::RealESRAnimeVideoV3x3,
+        #[cfg(feature = "model-realesr-animevideov3")]
+        Self::RealESRAnimeVideoV3x4,
+        #[cfg(feature = "model-realesrgan-plus")]
+        Self::RealESRGANPlusx4,
+        #[cfg(feature = "model-realesrgan-plus-anime")]
+        Self::RealESRGANPlusx4Anime,
+    ];
+
+    /// Model name as used by the upstream model files, e.g. `realesr-animevideov3-x2`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "model-realesr-animevideov3")]
+            Self::RealESRAnimeVideoV3x2 => "realesr-animevideov3-x2",
+            #[cfg(feature = "model-realesr-animevideov3")]
+            Self::RealESRAnimeVideoV3x3 => "realesr-animevideov3-x3",
+            #[cfg(feature = "model-realesr-animevideov3")]
+            Self::RealESRAnimeVideoV3x4 => "realesr-animevideov3-x4",
+            #[cfg(feature = "model-realesrgan-plus")]
+            Self::RealESRGANPlusx4 => "realesrgan-x4plus",
+            #[cfg(feature = "model-realesrgan-plus-anime")]
+            Self::RealESRGANPlusx4Anime => "realesrgan-x4plus-anime",
+        }
+    }
+
+    /// Looks a model up by its upstream name. Names without a scale suffix,
+    /// like `realesr-animevideov3`, resolve to the variant for `scale_factor`;
+    /// full names only match when their fixed scale is `scale_factor`.
+    pub fn from_name(name: &str, scale_factor: i32) -> Option<Self> {
+        let scaled_name = format!("{name}-x{scale_factor}");
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|model| model.name() == scaled_name || (model.name() == name && model.get_scale_factor() == scale_factor))
+    }
+
     pub const fn get_bytes(&self) -> (&'static [u8], &'static [u8]) {
         match self {
             #[cfg(feature = "model-realesr-animevideov3")]
@@ -78,18 +119,140 @@ impl OptionsModel {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OptionsScaleFactor {
     Double = 2,
     Triple = 3,
     Quadruple = 4,
 }
 
+impl TryFrom<i32> for OptionsScaleFactor {
+    type Error = i32;
+
+    fn try_from(scale_factor: i32) -> Result<Self, Self::Error> {
+        match scale_factor {
+            2 => Ok(Self::Double),
+            3 => Ok(Self::Triple),
+            4 => Ok(Self::Quadruple),
+            scale_factor => Err(scale_factor),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionsTileBlend {
+    /// Each tile keeps only its own area; the padding is cropped away.
+    Crop,
+    /// Neighbouring tiles overlap by half the tile padding and are cross-faded.
+    Linear,
+}
+
+/// Which flips and transposes each tile is upscaled under before the results are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsTtaMode {
+    #[default]
+    Off,
+    /// The tile and its mirror image, 2 passes.
+    Horizontal,
+    /// Every combination of horizontal and vertical flips, 4 passes.
+    Flips,
+    /// Flips and transposes, 8 passes.
+    Full,
+}
+
+impl OptionsTtaMode {
+    pub const fn passes(&self) -> usize {
+        match self {
+            Self::Off => 1,
+            Self::Horizontal => 2,
+            Self::Flips => 4,
+            Self::Full => 8,
+        }
+    }
+}
+
+impl From<bool> for OptionsTtaMode {
+    fn from(tta_mode: bool) -> Self {
+        if tta_mode { Self::Full } else { Self::Off }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsTtaMerge {
+    #[default]
+    Mean,
+    /// Per byte, which drops a single pass that hallucinated detail.
+    Median,
+}
+
+/// Classical interpolation used where the network output is blended or bypassed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsResampleFilter {
+    Bicubic,
+    #[default]
+    Lanczos,
+}
+
+/// Runs the model for its clean-up alone and returns an image the size of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsRestoreMode {
+    /// Plain upscaling.
+    #[default]
+    Off,
+    /// Upscale, then resample the result back down to the input size.
+    DownscaleOutput,
+    /// Shrink the input by the model scale first, so the model output is already about the input size.
+    /// Cheaper, and cleans up more aggressively.
+    DownscaleInput,
+}
+
+/// How the alpha channel of RGBA and gray-alpha images is upscaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsAlphaMode {
+    /// Smooth, but leaves halos around hard edges.
+    #[default]
+    Bicubic,
+    /// Keeps hard-edged sprites crisp.
+    Nearest,
+    /// Runs alpha through the network as a gray image, at the cost of a second pass.
+    Model,
+    /// Upscales premultiplied color so transparent pixels cannot bleed into the edges; alpha is bicubic.
+    Premultiplied,
+}
+
+/// Numeric precision of the network and of the pre/post-processing shaders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionsPrecision {
+    /// fp16 and int8 storage wherever the device supports them.
+    #[default]
+    Auto,
+    /// Full fp32 everywhere. Slowest, but exact on any device.
+    Fp32,
+    /// fp16 storage with fp32 arithmetic.
+    Fp16Storage,
+    /// fp16 storage and arithmetic. Fastest, at a small cost in accuracy.
+    Fp16Arithmetic,
+}
+
 #[derive(Debug)]
 pub struct Options<'a> {
     pub gpuid: i32,
-    pub tta_mode: bool,
-    pub tilesize: i32,
+    pub tta_mode: OptionsTtaMode,
+    pub tta_merge: OptionsTtaMerge,
+    pub tile_width: i32,
+    pub tile_height: i32,
+    pub adaptive_tilesize: bool,
+    pub tile_padding: i32,
+    pub tile_blend: OptionsTileBlend,
     pub scale_factor: i32,
+    pub precision: OptionsPrecision,
+    pub strength: f32,
+    pub weak_model: Option<(&'a [u8], &'a [u8])>,
+    pub resample_filter: OptionsResampleFilter,
+    pub restore_mode: OptionsRestoreMode,
+    pub alpha_mode: OptionsAlphaMode,
+    #[cfg(feature = "color-management")]
+    pub color_management: bool,
     pub param: &'a [u8],
     pub bin: &'a [u8],
 }
@@ -99,9 +262,22 @@ impl <'a>Default for Options<'a> {
     fn default() -> Self {
         Self {
             gpuid: 0,
-            tta_mode: false,
-            tilesize: 0,
+            tta_mode: OptionsTtaMode::Off,
+            tta_merge: OptionsTtaMerge::Mean,
+            tile_width: 0,
+            tile_height: 0,
+            adaptive_tilesize: false,
+            tile_padding: 10,
+            tile_blend: OptionsTileBlend::Crop,
             scale_factor: 4,
+            precision: OptionsPrecision::Auto,
+            strength: 1.0,
+            weak_model: None,
+            resample_filter: OptionsResampleFilter::Lanczos,
+            restore_mode: OptionsRestoreMode::Off,
+            alpha_mode: OptionsAlphaMode::Bicubic,
+            #[cfg(feature = "color-management")]
+            color_management: false,
             param: Self::DEFAULT_BYTES.0,
             bin: Self::DEFAULT_BYTES.1,
         }
@@ -144,18 +320,65 @@ impl <'a>Options<'a> {
         Ok(self)
     }
 
+    /// The model `strength` 0 stands for, e.g. `realesr-general-wdn-x4v3` next to `realesr-general-x4v3`.
+    /// It must share the main model's param file; below 1 the weights are interpolated rather than the pixels blended.
+    pub fn weak_model_bytes(mut self, param: &'a [u8], bin: &'a [u8]) -> Self {
+        self.weak_model = Some((param, bin));
+        self
+    }
+
+    pub fn weak_model_files<P: AsRef<Path>>(mut self, param_file: P, bin_file: P) -> Result<Self, std::io::Error> {
+        let param_file = std::fs::read(param_file)?;
+        let bin_file = std::fs::read(bin_file)?;
+        self.weak_model = Some((Box::leak(param_file.into_boxed_slice()), Box::leak(bin_file.into_boxed_slice())));
+        Ok(self)
+    }
+
     pub fn gpuid(mut self, gpuid: u8) -> Self {
         self.gpuid = gpuid as i32;
         self
     }
 
-    pub fn tta_mode(mut self, tta_mode: bool) -> Self {
-        self.tta_mode = tta_mode;
+    /// `true` and `false` map to `Full` and `Off`.
+    pub fn tta_mode<T: Into<OptionsTtaMode>>(mut self, tta_mode: T) -> Self {
+        self.tta_mode = tta_mode.into();
+        self
+    }
+
+    pub fn tta_merge(mut self, tta_merge: OptionsTtaMerge) -> Self {
+        self.tta_merge = tta_merge;
         self
     }
 
     pub fn tilesize(mut self, tilesize: usize) -> Self {
-        self.tilesize = tilesize as i32;
+        self.tile_width = tilesize as i32;
+        self.tile_height = tilesize as i32;
+        self
+    }
+
+    pub fn tile_width(mut self, tile_width: usize) -> Self {
+        self.tile_width = tile_width as i32;
+        self
+    }
+
+    pub fn tile_height(mut self, tile_height: usize) -> Self {
+        self.tile_height = tile_height as i32;
+        self
+    }
+
+    /// Halve the tile size and retry whenever the GPU runs out of memory.
+    pub fn adaptive_tilesize(mut self, adaptive_tilesize: bool) -> Self {
+        self.adaptive_tilesize = adaptive_tilesize;
+        self
+    }
+
+    pub fn tile_padding(mut self, tile_padding: usize) -> Self {
+        self.tile_padding = tile_padding as i32;
+        self
+    }
+
+    pub fn tile_blend(mut self, tile_blend: OptionsTileBlend) -> Self {
+        self.tile_blend = tile_blend;
         self
     }
 
@@ -164,4 +387,40 @@ impl <'a>Options<'a> {
         self
     }
 
+    /// Fails in `RealEsrgan::new` when the GPU lacks the requested fp16 support.
+    pub fn precision(mut self, precision: OptionsPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// How much of the network output to keep, from 0 (a plain `resample_filter` upscale) to 1 (the model alone).
+    /// With a `weak_model`, the weights of the two models are interpolated instead.
+    pub fn strength(mut self, strength: f32) -> Self {
+        self.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn resample_filter(mut self, resample_filter: OptionsResampleFilter) -> Self {
+        self.resample_filter = resample_filter;
+        self
+    }
+
+    /// Anything but `Off` makes every output the size of its input.
+    pub fn restore_mode(mut self, restore_mode: OptionsRestoreMode) -> Self {
+        self.restore_mode = restore_mode;
+        self
+    }
+
+    pub fn alpha_mode(mut self, alpha_mode: OptionsAlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Convert inputs with an embedded ICC profile to sRGB for inference and back afterwards.
+    #[cfg(feature = "color-management")]
+    pub fn color_management(mut self, color_management: bool) -> Self {
+        self.color_management = color_management;
+        self
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use crate::Error;
+use crate::{OptionsResampleFilter, RealEsrgan, TileBackend, Tiler};
+use crate::resample::resize;
+#[cfg(feature = "image")]
+use crate::realesrgan::process_image_channels;
+
+#[derive(Debug, Clone, Copy)]
+enum Step<'a> {
+    Upscale(&'a RealEsrgan),
+    Downscale(usize),
+}
+
+/// Chains upscalers for factors beyond a single model, e.g. 4x then 2x for 8x.
+/// The input is tiled once and every tile runs through all passes before the next
+/// one starts, so only the final output is ever held at full size. Region enhancers of
+/// the chained models run once on that stitched output, detecting in the pipeline input.
+#[derive(Debug, Clone)]
+pub struct Pipeline<'a> {
+    steps: Vec<Step<'a>>,
+    tiler: Option<Tiler>,
+    resample_filter: OptionsResampleFilter,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(realesrgan: &'a RealEsrgan) -> Self {
+        Self {
+            steps: vec![Step::Upscale(realesrgan)],
+            tiler: None,
+            resample_filter: OptionsResampleFilter::Lanczos,
+        }
+    }
+
+    pub fn then(mut self, realesrgan: &'a RealEsrgan) -> Self {
+        self.steps.push(Step::Upscale(realesrgan));
+        self
+    }
+
+    /// Shrinks the result of the passes so far by `factor`, e.g. 4x, 4x, then 2 down for 8x.
+    pub fn downscale(mut self, factor: usize) -> Self {
+        self.steps.push(Step::Downscale(factor));
+        self
+    }
+
+    pub fn resample_filter(mut self, resample_filter: OptionsResampleFilter) -> Self {
+        self.resample_filter = resample_filter;
+        self
+    }
+
+    /// Splits the input for the whole chain. By default the tile size, padding and blend of the first model are used.
+    pub fn tiler(mut self, tiler: Tiler) -> Self {
+        self.tiler = Some(tiler);
+        self
+    }
+
+    /// Every downscale has to divide the scale reached before it, so each tile comes out whole.
+    fn validate(&self) -> Result<usize, Error> {
+        let mut scale = 1;
+        for step in &self.steps {
+            match step {
+                Step::Upscale(realesrgan) => scale *= realesrgan.output_scale(),
+                Step::Downscale(factor) if *factor > 0 && scale % factor == 0 => scale /= factor,
+                Step::Downscale(factor) => {
+                    return Err(Error::InvalidPipeline(format!("cannot downscale {scale}x by {factor}")));
+                }
+            }
+        }
+        Ok(scale)
+    }
+
+    fn default_tiler(&self) -> Tiler {
+        let Some(Step::Upscale(first)) = self.steps.first() else {
+            unreachable!("a pipeline always starts with an upscale");
+        };
+        first.tiler()
+    }
+
+    pub fn process(&self, input: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        let expected_length = width * height;
+        if expected_length == 0 || !input.len().is_multiple_of(expected_length) {
+            return Err(Error::InvalidInput {
+                expected_length,
+                actual_length: input.len(),
+            });
+        }
+
+        self.process_channels(input, width, height, input.len() / expected_length)
+    }
+
+    pub(crate) fn process_channels(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        let scale = self.validate()?;
+        let tiler = self.tiler.clone().unwrap_or_else(|| self.default_tiler());
+        let mut output = tiler.process(self, input, width, height, channels)?;
+
+        for step in &self.steps {
+            if let Step::Upscale(realesrgan) = step {
+                realesrgan.enhance_output(input, &mut output, width, height, channels, scale)?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// Keeps the color type, like `RealEsrgan::process_image`.
+    #[cfg(feature = "image")]
+    pub fn process_image(&self, image: &crate::Image) -> Result<crate::Image, Error> {
+        let scale = self.validate()? as u32;
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        process_image_channels(image, scale, |input, channels| self.process_channels(input, width, height, channels))
+    }
+}
+
+impl TileBackend for Pipeline<'_> {
+    fn scale_factor(&self) -> usize {
+        self.validate().unwrap_or(0)
+    }
+
+    fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        let mut buffer = input.to_vec();
+        let (mut width, mut height) = (width, height);
+
+        for step in &self.steps {
+            match step {
+                Step::Upscale(realesrgan) => {
+                    buffer = realesrgan.process_stage(&buffer, width, height, channels)?;
+                    width *= realesrgan.output_scale();
+                    height *= realesrgan.output_scale();
+                }
+                Step::Downscale(factor) => {
+                    buffer = resize(&buffer, width, height, channels, width / factor, height / factor, self.resample_filter);
+                    width /= factor;
+                    height /= factor;
+                }
+            }
+        }
+
+        Ok(buffer)
+    }
+}
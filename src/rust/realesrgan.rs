@@ -1,18 +1,57 @@
 use crate::Options;
+use crate::OptionsTileBlend;
+use crate::OptionsPrecision;
+use crate::OptionsResampleFilter;
+use crate::OptionsAlphaMode;
+use crate::OptionsRestoreMode;
+use crate::{OptionsTtaMerge, OptionsTtaMode};
 use crate::Error;
+use crate::{ColorMatrix, ColorRange, ImageView, ProcessIter, RegionEnhancer, Resampler, TileBackend, Tiler, Tta, YuvFrame};
+use crate::enhance_regions;
+use crate::interpolate::interpolate_weights;
+use crate::resample::{resize, resize_nearest};
 
-use libc::{c_int, c_uchar, c_void, FILE};
+use std::cell::Cell;
+use std::ffi::CStr;
+
+use libc::{c_char, c_int, c_uchar, c_void, FILE};
+
+const ERROR_PARAM_PARSE_FAILED: c_int = -2;
+const ERROR_WEIGHT_SIZE_MISMATCH: c_int = -3;
+const ERROR_PIPELINE_CREATION_FAILED: c_int = -4;
+const ERROR_DEVICE_LOST: c_int = -5;
+const ERROR_NATIVE_EXCEPTION: c_int = -6;
+const ERROR_OUT_OF_DEVICE_MEMORY: c_int = -100;
+
+const PRECISION_AUTO: c_int = 0;
+const PRECISION_FP32: c_int = 1;
+const PRECISION_FP16_STORAGE: c_int = 2;
+const PRECISION_FP16_ARITHMETIC: c_int = 3;
+
+const FEATURE_FP16_STORAGE: c_int = 1;
+const FEATURE_FP16_ARITHMETIC: c_int = 2;
+
+const MIN_TILESIZE: usize = 16;
+
+#[cfg(test)]
+mod tests;
 
 extern "C" {
     fn realesrgan_init(
         gpuid: c_int,
         tta_mode: bool,
         scale: c_int,
-        tilesize: c_int,
+        precision: c_int,
     ) -> *mut c_void;
 
+    fn realesrgan_get_heap_budget(gpuid: c_int) -> u32;
+
+    fn realesrgan_get_last_exception() -> *const c_char;
+
     fn realesrgan_get_gpu_count() -> c_int;
 
+    fn realesrgan_get_gpu_features(gpuid: c_int) -> c_int;
+
     fn realesrgan_destroy_gpu_instance();
 
     fn realesrgan_free(realesrgan: *mut c_void);
@@ -23,13 +62,15 @@ extern "C" {
         model_path: *mut FILE
     ) -> c_int;
 
+    fn realesrgan_get_error_layer(realesrgan: *mut c_void) -> *const c_char;
+
     fn realesrgan_process(
         realesrgan: *mut c_void,
         in_image: *const c_uchar,
         out_image: *mut c_uchar,
         width: c_int,
         height: c_int,
-        channels: c_int,
+        format: NativePixelFormat,
     ) -> c_int;
 }
 
@@ -37,6 +78,19 @@ extern "C" {
 pub struct RealEsrgan {
     pointer: *mut c_void,
     scale_factor: i32,
+    tilesize: Cell<(usize, usize)>,
+    tile_padding: usize,
+    tile_blend: OptionsTileBlend,
+    tta_mode: OptionsTtaMode,
+    tta_merge: OptionsTtaMerge,
+    strength: f32,
+    resample_filter: OptionsResampleFilter,
+    restore_mode: OptionsRestoreMode,
+    alpha_mode: OptionsAlphaMode,
+    region_enhancer: Option<Box<dyn RegionEnhancer>>,
+    adaptive_tilesize: bool,
+    #[cfg(feature = "color-management")]
+    color_management: bool,
 }
 
 impl RealEsrgan {
@@ -46,6 +100,10 @@ impl RealEsrgan {
         }
         
         let count = unsafe { realesrgan_get_gpu_count() };
+        if count == ERROR_NATIVE_EXCEPTION {
+            return Err(Self::native_exception());
+        }
+
         if gpu >= count {
             unsafe { realesrgan_destroy_gpu_instance(); }
             Err(Error::GpuNotFound {
@@ -57,6 +115,19 @@ impl RealEsrgan {
         }
     }
 
+    fn validate_precision(gpu: i32, precision: OptionsPrecision) -> Result<(), Error> {
+        if required_features(precision) == 0 || gpu == -1 {
+            return Ok(());
+        }
+
+        let features = unsafe { realesrgan_get_gpu_features(gpu) };
+        if features == ERROR_NATIVE_EXCEPTION {
+            return Err(Self::native_exception());
+        }
+
+        supports_precision(gpu, features, precision)
+    }
+
     fn create_file_pointer(contents: &[u8]) -> *mut FILE {
         unsafe { 
             libc::fmemopen(
@@ -100,39 +171,146 @@ impl RealEsrgan {
             libc::fclose(file_bin_pointer);
         }
 
-        if result != 0 {
-            Err(Error::ModelLoadFailed { code: result })
+        load_status(result, || Self::error_layer(realesrgan), Self::last_exception)
+    }
+
+    fn last_exception() -> Option<String> {
+        let message = unsafe { realesrgan_get_last_exception() };
+        if message.is_null() {
+            return None;
+        }
+
+        let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+        if message.is_empty() {
+            None
         } else {
-            Ok(())
+            Some(message.into_owned())
+        }
+    }
+
+    fn native_exception() -> Error {
+        Error::NativeException(Self::last_exception().unwrap_or_default())
+    }
+
+    fn error_layer(realesrgan: *mut c_void) -> Option<String> {
+        let layer = unsafe { realesrgan_get_error_layer(realesrgan) };
+        if layer.is_null() {
+            return None;
+        }
+
+        let layer = unsafe { CStr::from_ptr(layer) }.to_string_lossy();
+        if layer.is_empty() {
+            None
+        } else {
+            Some(layer.into_owned())
         }
     }
 
+    fn default_tilesize(gpuid: i32) -> usize {
+        tilesize_for_budget(unsafe { realesrgan_get_heap_budget(gpuid) })
+    }
+
     pub fn new(options: Options) -> Result<Self, Error> {
         Self::validate_gpu(options.gpuid)?;
+        Self::validate_precision(options.gpuid, options.precision)?;
+
+        let precision = precision_code(options.precision);
+        let strength = options.strength.clamp(0.0, 1.0);
+
+        // the network itself is toned down when there is a weak model to interpolate with
+        let interpolated = match options.weak_model {
+            Some(weak_model) if strength < 1.0 => Some(interpolate_weights((options.param, options.bin), weak_model, strength)?),
+            _ => None,
+        };
+        let bin = interpolated.as_deref().unwrap_or(options.bin);
+
+        // the shaders already average all eight variants, anything else runs through Tta
+        let native_tta = options.tta_mode == OptionsTtaMode::Full && options.tta_merge == OptionsTtaMerge::Mean;
 
         let pointer = unsafe {
             realesrgan_init(
                 options.gpuid,
-                options.tta_mode,
+                native_tta,
                 options.scale_factor,
-                options.tilesize
+                precision,
             )
         };
 
         if pointer.is_null() {
-            return Err(Error::InitializationFailed);
+            return Err(Self::last_exception()
+                .map(Error::NativeException)
+                .unwrap_or(Error::InitializationFailed));
         }
 
-        Self::load_model(pointer, options.param, options.bin)?;
+        if let Err(error) = Self::load_model(pointer, options.param, bin) {
+            unsafe { realesrgan_free(pointer) };
+            return Err(error);
+        }
 
         let scale_factor = options.scale_factor;
+        let tile_width = match options.tile_width {
+            0 => Self::default_tilesize(options.gpuid),
+            tile_width => tile_width as usize,
+        };
+        let tile_height = match options.tile_height {
+            0 => Self::default_tilesize(options.gpuid),
+            tile_height => tile_height as usize,
+        };
 
         Ok(Self {
             pointer,
             scale_factor,
+            tilesize: Cell::new((tile_width, tile_height)),
+            tile_padding: options.tile_padding as usize,
+            tile_blend: options.tile_blend,
+            tta_mode: if native_tta { OptionsTtaMode::Off } else { options.tta_mode },
+            tta_merge: options.tta_merge,
+            strength: if interpolated.is_some() { 1.0 } else { strength },
+            resample_filter: options.resample_filter,
+            restore_mode: options.restore_mode,
+            alpha_mode: options.alpha_mode,
+            region_enhancer: None,
+            adaptive_tilesize: options.adaptive_tilesize,
+            #[cfg(feature = "color-management")]
+            color_management: options.color_management,
         })
     }
 
+    /// Restores the regions `enhancer` detects once the whole image is upscaled, e.g. faces.
+    pub fn region_enhancer<E: RegionEnhancer + 'static>(mut self, enhancer: E) -> Self {
+        self.region_enhancer = Some(Box::new(enhancer));
+        self
+    }
+
+    /// How much larger outputs are than inputs: the model scale, or 1 with a restore mode.
+    pub fn output_scale(&self) -> usize {
+        match self.restore_mode {
+            OptionsRestoreMode::Off => self.scale_factor as usize,
+            OptionsRestoreMode::DownscaleOutput | OptionsRestoreMode::DownscaleInput => 1,
+        }
+    }
+
+    pub fn tilesize(&self) -> (usize, usize) {
+        self.tilesize.get()
+    }
+
+    fn shrink_tilesize(&self) -> bool {
+        match halve_tilesize(self.tilesize.get()) {
+            Some(tilesize) => {
+                self.tilesize.set(tilesize);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn tiler(&self) -> Tiler {
+        let (tile_width, tile_height) = self.tilesize.get();
+        Tiler::new(tile_width, tile_height)
+            .padding(self.tile_padding)
+            .blend(self.tile_blend)
+    }
+
     pub fn process(&self, input: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
         if self.pointer.is_null() {
             return Err(Error::InvalidPointer);
@@ -141,7 +319,7 @@ impl RealEsrgan {
         let input_length = input.len();
         let expected_length = width * height;
         
-        if input_length % expected_length != 0 {
+        if expected_length == 0 || !input_length.is_multiple_of(expected_length) {
             return Err(Error::InvalidInput {
                 expected_length,
                 actual_length: input_length
@@ -149,28 +327,196 @@ impl RealEsrgan {
         }
 
         let channels = input_length / expected_length;
-        let output_length = (width * self.scale_factor as usize) 
-                          * (height * self.scale_factor as usize) 
-                          * channels;
+        self.process_channels(input, width, height, channels)
+    }
 
-        let mut output = vec![0u8; output_length];
+    pub fn process_view(&self, view: ImageView) -> Result<Vec<u8>, Error> {
+        if self.pointer.is_null() {
+            return Err(Error::InvalidPointer);
+        }
 
-        let code = unsafe {
-            realesrgan_process(
-                self.pointer,
-                input.as_ptr(),
-                output.as_mut_ptr(),
-                width as c_int,
-                height as c_int,
-                channels as c_int,
-            )
-        };
+        let expected_length = view.expected_length();
+        if expected_length == 0 || view.data.len() != expected_length {
+            return Err(Error::InvalidInput {
+                expected_length,
+                actual_length: view.data.len(),
+            });
+        }
+
+        let channels = view.format.channels();
+        if !view.format.is_premultiplied() {
+            return self.process_ordered(view.data, view.width, view.height, channels, view.format.is_bgr());
+        }
+
+        // the network was trained on straight color, premultiplied edges would come out dark
+        let mut straight = view.data.to_vec();
+        unpremultiply(&mut straight, channels);
+        let mut output = self.process_ordered(&straight, view.width, view.height, channels, view.format.is_bgr())?;
+        premultiply(&mut output, channels);
+        Ok(output)
+    }
+
+    /// Gray is run through the network as RGB and collapsed back by luminance. Alpha is split off and
+    /// upscaled by `alpha_mode`, except RGBA with `Bicubic`, which the GPU handles on its own.
+    pub(crate) fn process_channels(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        self.process_ordered(input, width, height, channels, false)
+    }
+
+    /// `bgr` declares BGR or BGRA input, which comes back in the same order.
+    fn process_ordered(&self, input: &[u8], width: usize, height: usize, channels: usize, bgr: bool) -> Result<Vec<u8>, Error> {
+        let mut output = self.process_unenhanced(input, width, height, channels, bgr)?;
+        self.enhance_output(input, &mut output, width, height, channels, self.output_scale())?;
+        Ok(output)
+    }
+
+    /// One stage of a `Pipeline`, which runs the region enhancer itself on the stitched image.
+    pub(crate) fn process_stage(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        self.process_unenhanced(input, width, height, channels, false)
+    }
 
-        if code == 0 {
-            Ok(output)
+    /// Runs the region enhancer, if any, over `output`, the `scale`x upscale of the whole `input`.
+    pub(crate) fn enhance_output(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+        scale: usize,
+    ) -> Result<(), Error> {
+        match &self.region_enhancer {
+            Some(enhancer) => enhance_regions(enhancer.as_ref(), input, output, width, height, channels, scale),
+            None => Ok(()),
+        }
+    }
+
+    fn process_unenhanced(&self, input: &[u8], width: usize, height: usize, channels: usize, bgr: bool) -> Result<Vec<u8>, Error> {
+        match channels {
+            1 => self.process_gray(input, width, height),
+            4 if self.alpha_mode == OptionsAlphaMode::Bicubic => self.process_color(input, width, height, channels, bgr),
+            2 | 4 => self.process_alpha(input, width, height, channels, bgr),
+            _ => self.process_color(input, width, height, channels, bgr),
+        }
+    }
+
+    fn process_gray(&self, input: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        let rgb: Vec<u8> = input.iter().flat_map(|luma| [*luma; 3]).collect();
+        let output = self.process_color(&rgb, width, height, 3, false)?;
+        Ok(output.chunks_exact(3).map(luminance).collect())
+    }
+
+    fn process_alpha(&self, input: &[u8], width: usize, height: usize, channels: usize, bgr: bool) -> Result<Vec<u8>, Error> {
+        let premultiplied = self.alpha_mode == OptionsAlphaMode::Premultiplied;
+        let mut premultiplied_input = Vec::new();
+        let input = if premultiplied {
+            premultiplied_input.extend_from_slice(input);
+            premultiply(&mut premultiplied_input, channels);
+            &premultiplied_input
         } else {
-            Err(Error::ProcessingFailed { code })
+            input
+        };
+
+        let color_channels = channels - 1;
+        let color: Vec<u8> = input.chunks_exact(channels).flat_map(|pixel| &pixel[..color_channels]).copied().collect();
+        let alpha: Vec<u8> = input.chunks_exact(channels).map(|pixel| pixel[color_channels]).collect();
+
+        let color = match color_channels {
+            1 => self.process_gray(&color, width, height)?,
+            _ => self.process_color(&color, width, height, color_channels, bgr)?,
+        };
+
+        let scale = self.output_scale();
+        let (output_width, output_height) = (width * scale, height * scale);
+        let alpha = match self.alpha_mode {
+            OptionsAlphaMode::Nearest => resize_nearest(&alpha, width, height, 1, output_width, output_height),
+            OptionsAlphaMode::Model => self.process_alpha_plane(&alpha, width, height)?,
+            OptionsAlphaMode::Bicubic | OptionsAlphaMode::Premultiplied => {
+                resize(&alpha, width, height, 1, output_width, output_height, OptionsResampleFilter::Bicubic)
+            }
+        };
+
+        let mut output: Vec<u8> = color
+            .chunks_exact(color_channels)
+            .zip(alpha)
+            .flat_map(|(pixel, alpha)| pixel.iter().copied().chain([alpha]))
+            .collect();
+        if premultiplied {
+            unpremultiply(&mut output, channels);
+        }
+        Ok(output)
+    }
+
+    fn process_color(&self, input: &[u8], width: usize, height: usize, channels: usize, bgr: bool) -> Result<Vec<u8>, Error> {
+        self.restore(input, width, height, channels, |input, width, height| {
+            let output = self.infer(input, width, height, channels, bgr)?;
+            Ok(self.blend_strength(output, input, width, height, channels))
+        })
+    }
+
+    /// The alpha of `OptionsAlphaMode::Model` only goes through the network and the restore mode,
+    /// never strength blending or region enhancement.
+    fn process_alpha_plane(&self, alpha: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        self.restore(alpha, width, height, 1, |alpha, width, height| {
+            let rgb: Vec<u8> = alpha.iter().flat_map(|value| [*value; 3]).collect();
+            let output = self.infer(&rgb, width, height, 3, false)?;
+            Ok(output.chunks_exact(3).map(luminance).collect())
+        })
+    }
+
+    /// Applies the restore mode around `upscale`, which runs at the model scale.
+    fn restore<F>(&self, input: &[u8], width: usize, height: usize, channels: usize, upscale: F) -> Result<Vec<u8>, Error>
+    where
+        F: Fn(&[u8], usize, usize) -> Result<Vec<u8>, Error>,
+    {
+        let scale = self.scale_factor as usize;
+        match self.restore_mode {
+            OptionsRestoreMode::Off => upscale(input, width, height),
+            OptionsRestoreMode::DownscaleOutput => {
+                let output = upscale(input, width, height)?;
+                Ok(resize(&output, width * scale, height * scale, channels, width, height, self.resample_filter))
+            }
+            OptionsRestoreMode::DownscaleInput => {
+                let (small_width, small_height) = (width.div_ceil(scale), height.div_ceil(scale));
+                let small = resize(input, width, height, channels, small_width, small_height, self.resample_filter);
+                let output = upscale(&small, small_width, small_height)?;
+                if (small_width * scale, small_height * scale) == (width, height) {
+                    return Ok(output);
+                }
+                Ok(resize(&output, small_width * scale, small_height * scale, channels, width, height, self.resample_filter))
+            }
+        }
+    }
+
+    /// Tiles `input` through the network and `Tta`, shrinking the tiles on out-of-memory when allowed.
+    fn infer(&self, input: &[u8], width: usize, height: usize, channels: usize, bgr: bool) -> Result<Vec<u8>, Error> {
+        let native = NativeBackend { realesrgan: self, bgr };
+        let backend = Tta::new(&native, self.tta_mode).merge(self.tta_merge);
+        loop {
+            match self.tiler().process(&backend, input, width, height, channels) {
+                Err(Error::OutOfDeviceMemory { .. }) if self.adaptive_tilesize && self.shrink_tilesize() => continue,
+                Err(Error::OutOfDeviceMemory { .. }) => {
+                    let (tile_width, tile_height) = self.tilesize.get();
+                    return Err(Error::OutOfDeviceMemory {
+                        tile_width: tile_width as i32,
+                        tile_height: tile_height as i32,
+                    });
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn blend_strength(&self, mut output: Vec<u8>, input: &[u8], width: usize, height: usize, channels: usize) -> Vec<u8> {
+        if self.strength >= 1.0 {
+            return output;
+        }
+
+        let classical = Resampler::new(self.scale_factor as usize, self.resample_filter).upscale(input, width, height, channels);
+        for (value, classical) in output.iter_mut().zip(classical) {
+            let blended = classical as f32 + (*value as f32 - classical as f32) * self.strength;
+            *value = blended.round() as u8;
         }
+        output
     }
 
     pub fn process_batch<I, B>(
@@ -189,6 +535,78 @@ impl RealEsrgan {
             .collect()
     }
 
+    /// Yields each upscaled buffer as it is produced instead of collecting the whole batch,
+    /// reading up to `lookahead` inputs ahead on a background thread.
+    pub fn process_iter<I, B>(
+        &self,
+        inputs: I,
+        width: usize,
+        height: usize,
+        lookahead: usize,
+    ) -> ProcessIter<'_, B>
+    where
+        I: IntoIterator<Item = B>,
+        I::IntoIter: Send + 'static,
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        ProcessIter::new(self, inputs, width, height, lookahead)
+    }
+
+    /// Moves the upscaler onto its own thread and streams the results back,
+    /// buffering at most `lookahead` of them.
+    #[cfg(feature = "async")]
+    pub fn process_stream<I, B>(
+        self,
+        inputs: I,
+        width: usize,
+        height: usize,
+        lookahead: usize,
+    ) -> crate::ProcessStream
+    where
+        I: IntoIterator<Item = B>,
+        I::IntoIter: Send + 'static,
+        B: AsRef<[u8]>,
+    {
+        crate::ProcessStream::new(self, inputs, width, height, lookahead)
+    }
+
+    /// Upscales a planar YUV frame through RGB, keeping its chroma sampling.
+    pub fn process_yuv(&self, frame: &YuvFrame, matrix: ColorMatrix, range: ColorRange) -> Result<YuvFrame, Error> {
+        if frame.data.len() != frame.expected_length() {
+            return Err(Error::InvalidInput {
+                expected_length: frame.expected_length(),
+                actual_length: frame.data.len(),
+            });
+        }
+
+        let scale = self.output_scale();
+        let rgb = self.process_channels(&frame.to_rgb(matrix, range), frame.width, frame.height, 3)?;
+        Ok(YuvFrame::from_rgb(&rgb, frame.width * scale, frame.height * scale, frame.sampling, matrix, range))
+    }
+
+    /// Upscales images of differing sizes and layouts. Every item gets its own
+    /// result, so one bad input does not abort the rest of the batch.
+    pub fn process_views<'a, I>(&self, inputs: I) -> Vec<Result<Vec<u8>, Error>>
+    where
+        I: IntoIterator<Item = ImageView<'a>>,
+    {
+        inputs
+            .into_iter()
+            .map(|view| self.process_view(view))
+            .collect()
+    }
+
+    #[cfg(feature = "image")]
+    pub fn process_images<'a, I>(&self, images: I) -> Vec<Result<crate::Image, Error>>
+    where
+        I: IntoIterator<Item = &'a crate::Image>,
+    {
+        images
+            .into_iter()
+            .map(|image| self.process_image(image))
+            .collect()
+    }
+
     #[cfg(feature = "image")]
     pub fn process_file<P>(&self, path: P) -> Result<crate::Image, Error>
     where 
@@ -198,27 +616,270 @@ impl RealEsrgan {
         self.process_image(&image)
     }
 
+    /// Upscales `input` into `output`, picking the format from the output extension.
+    /// The image is rotated upright from its EXIF orientation first. Its ICC profile,
+    /// EXIF and XMP are carried over, with the DPI scaled so the physical size stays the same.
+    #[cfg(feature = "image")]
+    pub fn process_file_to<P, Q>(&self, input: P, output: Q) -> Result<(), Error>
+    where
+        P: AsRef<std::path::Path>,
+        Q: AsRef<std::path::Path>,
+    {
+        let output = output.as_ref();
+        let format = image::ImageFormat::from_path(output)
+            .map_err(|error| Error::ImageSaveFailed(format!("{}: {error}", output.display())))?;
+
+        let (image, mut metadata) = crate::metadata::open(input.as_ref())?;
+        let upscaled = match metadata.icc_profile.as_deref() {
+            #[cfg(feature = "color-management")]
+            Some(icc_profile) if self.color_management => self.process_image_with_profile(&image, icc_profile)?,
+            _ => self.process_image(&image)?,
+        };
+        metadata.scale(self.output_scale() as u32, upscaled.width(), upscaled.height());
+        crate::metadata::save(&upscaled, &metadata, output, format)
+    }
+
+    /// Upscales an image whose pixels are in the color space of `icc_profile`,
+    /// running the network on an sRGB copy and converting the result back.
+    #[cfg(feature = "color-management")]
+    pub fn process_image_with_profile(&self, image: &crate::Image, icc_profile: &[u8]) -> Result<crate::Image, Error> {
+        let color_space = crate::color::ColorSpace::new(icc_profile)?;
+        let upscaled = self.process_image(&color_space.to_srgb(image)?)?;
+        color_space.to_profile(&upscaled)
+    }
+
+    /// Upscales every frame of an animation, keeping frame delays and the loop count.
+    #[cfg(feature = "image")]
+    pub fn process_animation(&self, animation: &crate::Animation) -> Result<crate::Animation, Error> {
+        let scale = self.output_scale() as u32;
+        let frames = animation
+            .frames
+            .iter()
+            .map(|frame| {
+                let buffer = frame.buffer();
+                let (width, height) = buffer.dimensions();
+                let output = self.process_channels(buffer.as_raw(), width as usize, height as usize, 4)?;
+                let buffer = image::RgbaImage::from_raw(width * scale, height * scale, output).ok_or(Error::ColorConversionFailed)?;
+                Ok(image::Frame::from_parts(buffer, frame.left() * scale, frame.top() * scale, frame.delay()))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(crate::Animation {
+            frames,
+            loop_count: animation.loop_count,
+        })
+    }
+
     #[cfg(feature = "image")]
     pub fn process_image(&self, image: &crate::Image) -> Result<crate::Image, Error> {
-        use image::{ColorType, ImageBuffer, DynamicImage};
-
-        let color_type = image.color();
-        let input = image.to_rgb8().into_raw();
-        let width = image.width();
-        let height = image.height();
-        let output = self.process(&input, width as usize, height as usize)?;
-        let new_width = width * self.scale_factor as u32;
-        let new_height = height * self.scale_factor as u32;
-    
-        let dynamic_image = match color_type {
-            ColorType::Rgb8 => ImageBuffer::from_raw(new_width, new_height, output).map(DynamicImage::ImageRgb8),
-            ColorType::Rgba8 => ImageBuffer::from_raw(new_width, new_height, output).map(DynamicImage::ImageRgba8),
-            ColorType::L8 => ImageBuffer::from_raw(new_width, new_height, output).map(DynamicImage::ImageLuma8),
-            ColorType::La8 => ImageBuffer::from_raw(new_width, new_height, output).map(DynamicImage::ImageLumaA8),
-            _ => ImageBuffer::from_raw(new_width, new_height, output).map(DynamicImage::ImageRgb8),
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        process_image_channels(image, self.output_scale() as u32, |input, channels| {
+            self.process_channels(input, width, height, channels)
+        })
+    }
+}
+
+/// Runs `process` on the pixels of `image` in the layout closest to its own and rebuilds
+/// an image of the same color type, `scale` times larger.
+#[cfg(feature = "image")]
+pub(crate) fn process_image_channels<F>(image: &crate::Image, scale: u32, process: F) -> Result<crate::Image, Error>
+where
+    F: Fn(&[u8], usize) -> Result<Vec<u8>, Error>,
+{
+    use image::{ColorType, ImageBuffer, DynamicImage};
+
+    let new_width = image.width() * scale;
+    let new_height = image.height() * scale;
+    // inference is 8-bit, 16-bit gray only gets its depth back
+    let widen = |output: Vec<u8>| output.into_iter().map(|value| value as u16 * 257).collect::<Vec<u16>>();
+
+    let dynamic_image = match image.color() {
+        ColorType::L8 => ImageBuffer::from_raw(new_width, new_height, process(image.to_luma8().as_raw(), 1)?).map(DynamicImage::ImageLuma8),
+        ColorType::La8 => ImageBuffer::from_raw(new_width, new_height, process(image.to_luma_alpha8().as_raw(), 2)?).map(DynamicImage::ImageLumaA8),
+        ColorType::L16 => ImageBuffer::from_raw(new_width, new_height, widen(process(image.to_luma8().as_raw(), 1)?)).map(DynamicImage::ImageLuma16),
+        ColorType::La16 => ImageBuffer::from_raw(new_width, new_height, widen(process(image.to_luma_alpha8().as_raw(), 2)?)).map(DynamicImage::ImageLumaA16),
+        color_type if color_type.has_alpha() => ImageBuffer::from_raw(new_width, new_height, process(image.to_rgba8().as_raw(), 4)?).map(DynamicImage::ImageRgba8),
+        _ => ImageBuffer::from_raw(new_width, new_height, process(image.to_rgb8().as_raw(), 3)?).map(DynamicImage::ImageRgb8),
+    };
+
+    dynamic_image.ok_or(Error::ColorConversionFailed)
+}
+
+/// Maps the status of `realesrgan_load_files`. `layer` and `exception` are only asked for by the codes that carry them.
+fn load_status<L, X>(code: c_int, layer: L, exception: X) -> Result<(), Error>
+where
+    L: FnOnce() -> Option<String>,
+    X: FnOnce() -> Option<String>,
+{
+    match code {
+        0 => Ok(()),
+        ERROR_PARAM_PARSE_FAILED => Err(Error::ParamParseFailed),
+        ERROR_WEIGHT_SIZE_MISMATCH => Err(Error::WeightSizeMismatch { layer: layer() }),
+        ERROR_PIPELINE_CREATION_FAILED => Err(Error::PipelineCreationFailed { layer: layer() }),
+        ERROR_NATIVE_EXCEPTION => Err(Error::NativeException(exception().unwrap_or_default())),
+        code => Err(Error::ModelLoadFailed { code }),
+    }
+}
+
+/// Maps the status of `realesrgan_process` for a `width`x`height` tile.
+fn process_status<X>(code: c_int, width: usize, height: usize, exception: X) -> Result<(), Error>
+where
+    X: FnOnce() -> Option<String>,
+{
+    match code {
+        0 => Ok(()),
+        ERROR_OUT_OF_DEVICE_MEMORY => Err(Error::OutOfDeviceMemory {
+            tile_width: width as i32,
+            tile_height: height as i32,
+        }),
+        ERROR_DEVICE_LOST => Err(Error::DeviceLost),
+        ERROR_NATIVE_EXCEPTION => Err(Error::NativeException(exception().unwrap_or_default())),
+        code => Err(Error::ProcessingFailed { code }),
+    }
+}
+
+const fn precision_code(precision: OptionsPrecision) -> c_int {
+    match precision {
+        OptionsPrecision::Auto => PRECISION_AUTO,
+        OptionsPrecision::Fp32 => PRECISION_FP32,
+        OptionsPrecision::Fp16Storage => PRECISION_FP16_STORAGE,
+        OptionsPrecision::Fp16Arithmetic => PRECISION_FP16_ARITHMETIC,
+    }
+}
+
+/// The `realesrgan_get_gpu_features` bits `precision` needs, none for the modes every GPU runs.
+const fn required_features(precision: OptionsPrecision) -> c_int {
+    match precision {
+        OptionsPrecision::Auto | OptionsPrecision::Fp32 => 0,
+        OptionsPrecision::Fp16Storage => FEATURE_FP16_STORAGE,
+        OptionsPrecision::Fp16Arithmetic => FEATURE_FP16_STORAGE | FEATURE_FP16_ARITHMETIC,
+    }
+}
+
+fn supports_precision(gpu: i32, features: c_int, precision: OptionsPrecision) -> Result<(), Error> {
+    let required = required_features(precision);
+    if features & required == required {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedPrecision { gpuid: gpu, precision })
+    }
+}
+
+/// The automatic tile size for a device heap budget in MB.
+const fn tilesize_for_budget(heap_budget: u32) -> usize {
+    if heap_budget > 1900 {
+        200
+    } else if heap_budget > 550 {
+        100
+    } else if heap_budget > 190 {
+        64
+    } else {
+        32
+    }
+}
+
+/// The next tile size to retry with after running out of memory, `None` once both sides are at the minimum.
+fn halve_tilesize((tile_width, tile_height): (usize, usize)) -> Option<(usize, usize)> {
+    if tile_width <= MIN_TILESIZE && tile_height <= MIN_TILESIZE {
+        return None;
+    }
+    Some(((tile_width / 2).max(MIN_TILESIZE), (tile_height / 2).max(MIN_TILESIZE)))
+}
+
+/// Scales the color of every pixel by its alpha, the last channel.
+fn premultiply(pixels: &mut [u8], channels: usize) {
+    for pixel in pixels.chunks_exact_mut(channels) {
+        let (color, alpha) = pixel.split_at_mut(channels - 1);
+        let alpha = alpha[0] as u32;
+        for value in color {
+            *value = ((*value as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+fn unpremultiply(pixels: &mut [u8], channels: usize) {
+    for pixel in pixels.chunks_exact_mut(channels) {
+        let (color, alpha) = pixel.split_at_mut(channels - 1);
+        let alpha = alpha[0] as u32;
+        for value in color {
+            *value = match alpha {
+                0 => 0,
+                alpha => ((*value as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+            };
+        }
+    }
+}
+
+/// Rec. 601 luma of an RGB pixel.
+pub(crate) fn luminance(pixel: &[u8]) -> u8 {
+    let luma = 0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32;
+    luma.round().clamp(0.0, 255.0) as u8
+}
+
+#[repr(C)]
+struct NativePixelFormat {
+    channels: c_int,
+    bgr: bool,
+}
+
+/// The ncnn backend for one channel order.
+struct NativeBackend<'a> {
+    realesrgan: &'a RealEsrgan,
+    bgr: bool,
+}
+
+impl TileBackend for NativeBackend<'_> {
+    fn scale_factor(&self) -> usize {
+        self.realesrgan.scale_factor as usize
+    }
+
+    fn process_tile(
+        &self,
+        input: &[u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let scale_factor = self.scale_factor();
+        let output_length = (width * scale_factor)
+                          * (height * scale_factor)
+                          * channels;
+
+        let mut output = vec![0u8; output_length];
+
+        let format = NativePixelFormat {
+            channels: channels as c_int,
+            bgr: self.bgr,
         };
-    
-        dynamic_image.ok_or(Error::ColorConversionFailed)
+
+        let code = unsafe {
+            realesrgan_process(
+                self.realesrgan.pointer,
+                input.as_ptr(),
+                output.as_mut_ptr(),
+                width as c_int,
+                height as c_int,
+                format,
+            )
+        };
+
+        process_status(code, width, height, RealEsrgan::last_exception).map(|()| output)
+    }
+}
+
+impl TileBackend for RealEsrgan {
+    fn scale_factor(&self) -> usize {
+        self.scale_factor as usize
+    }
+
+    fn process_tile(
+        &self,
+        input: &[u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> Result<Vec<u8>, Error> {
+        NativeBackend { realesrgan: self, bgr: false }.process_tile(input, width, height, channels)
     }
 }
 
@@ -0,0 +1,97 @@
+use super::*;
+
+fn no_message() -> Option<String> {
+    None
+}
+
+#[test]
+fn load_status_maps_native_codes() {
+    let layer = || Some("conv_3".to_string());
+
+    assert!(load_status(0, layer, no_message).is_ok());
+    assert!(matches!(load_status(ERROR_PARAM_PARSE_FAILED, layer, no_message), Err(Error::ParamParseFailed)));
+    assert!(matches!(
+        load_status(ERROR_WEIGHT_SIZE_MISMATCH, layer, no_message),
+        Err(Error::WeightSizeMismatch { layer: Some(name) }) if name == "conv_3"
+    ));
+    assert!(matches!(
+        load_status(ERROR_PIPELINE_CREATION_FAILED, no_message, no_message),
+        Err(Error::PipelineCreationFailed { layer: None })
+    ));
+    assert!(matches!(load_status(-1, layer, no_message), Err(Error::ModelLoadFailed { code: -1 })));
+}
+
+#[test]
+fn load_status_asks_for_details_only_when_needed() {
+    let layer = || -> Option<String> { panic!("layer asked for") };
+    let exception = || -> Option<String> { panic!("exception asked for") };
+
+    assert!(load_status(0, layer, exception).is_ok());
+    assert!(matches!(load_status(ERROR_PARAM_PARSE_FAILED, layer, exception), Err(Error::ParamParseFailed)));
+}
+
+#[test]
+fn native_exception_carries_its_message() {
+    let exception = || Some("vkQueueSubmit failed".to_string());
+
+    assert!(matches!(
+        load_status(ERROR_NATIVE_EXCEPTION, no_message, exception),
+        Err(Error::NativeException(message)) if message == "vkQueueSubmit failed"
+    ));
+    assert!(matches!(
+        process_status(ERROR_NATIVE_EXCEPTION, 64, 64, no_message),
+        Err(Error::NativeException(message)) if message.is_empty()
+    ));
+}
+
+#[test]
+fn process_status_maps_native_codes() {
+    assert!(process_status(0, 64, 32, no_message).is_ok());
+    assert!(matches!(
+        process_status(ERROR_OUT_OF_DEVICE_MEMORY, 64, 32, no_message),
+        Err(Error::OutOfDeviceMemory { tile_width: 64, tile_height: 32 })
+    ));
+    assert!(matches!(process_status(ERROR_DEVICE_LOST, 64, 32, no_message), Err(Error::DeviceLost)));
+    assert!(matches!(process_status(-1, 64, 32, no_message), Err(Error::ProcessingFailed { code: -1 })));
+}
+
+#[test]
+fn precision_maps_to_native_constants() {
+    assert_eq!(precision_code(OptionsPrecision::Auto), PRECISION_AUTO);
+    assert_eq!(precision_code(OptionsPrecision::Fp32), PRECISION_FP32);
+    assert_eq!(precision_code(OptionsPrecision::Fp16Storage), PRECISION_FP16_STORAGE);
+    assert_eq!(precision_code(OptionsPrecision::Fp16Arithmetic), PRECISION_FP16_ARITHMETIC);
+}
+
+#[test]
+fn precision_checks_gpu_features() {
+    assert!(supports_precision(0, 0, OptionsPrecision::Auto).is_ok());
+    assert!(supports_precision(0, 0, OptionsPrecision::Fp32).is_ok());
+    assert!(supports_precision(0, FEATURE_FP16_STORAGE, OptionsPrecision::Fp16Storage).is_ok());
+    assert!(matches!(
+        supports_precision(1, FEATURE_FP16_STORAGE, OptionsPrecision::Fp16Arithmetic),
+        Err(Error::UnsupportedPrecision { gpuid: 1, precision: OptionsPrecision::Fp16Arithmetic })
+    ));
+    assert!(matches!(
+        supports_precision(0, FEATURE_FP16_ARITHMETIC, OptionsPrecision::Fp16Storage),
+        Err(Error::UnsupportedPrecision { .. })
+    ));
+    assert!(supports_precision(0, FEATURE_FP16_STORAGE | FEATURE_FP16_ARITHMETIC, OptionsPrecision::Fp16Arithmetic).is_ok());
+}
+
+#[test]
+fn tilesize_follows_heap_budget() {
+    assert_eq!(tilesize_for_budget(0), 32);
+    assert_eq!(tilesize_for_budget(190), 32);
+    assert_eq!(tilesize_for_budget(191), 64);
+    assert_eq!(tilesize_for_budget(551), 100);
+    assert_eq!(tilesize_for_budget(1901), 200);
+}
+
+#[test]
+fn tilesize_halves_down_to_the_minimum() {
+    assert_eq!(halve_tilesize((200, 100)), Some((100, 50)));
+    assert_eq!(halve_tilesize((40, 16)), Some((20, 16)));
+    assert_eq!(halve_tilesize((20, 16)), Some((16, 16)));
+    assert_eq!(halve_tilesize((16, 16)), None);
+}
@@ -0,0 +1,87 @@
+use crate::Error;
+
+use std::fmt;
+
+/// A box in input pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Restores parts of the upscaled image with a second model, e.g. faces with GFPGAN.
+pub trait RegionEnhancer: Send {
+    /// Boxes to enhance, found in the original input.
+    fn detect(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<Region>, Error>;
+
+    /// Returns the enhanced crop, which is cut from the whole upscaled image and must keep its size.
+    /// Crops have the input's channels, in BGR order for BGR input.
+    fn enhance(&self, crop: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error>;
+}
+
+impl fmt::Debug for dyn RegionEnhancer {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("RegionEnhancer")
+    }
+}
+
+/// Runs `enhancer` over `output`, the `scale`x upscale of `input`, and pastes each crop back
+/// with its edges feathered over an eighth of the box so no seam shows.
+pub fn enhance_regions<E>(
+    enhancer: &E,
+    input: &[u8],
+    output: &mut [u8],
+    width: usize,
+    height: usize,
+    channels: usize,
+    scale: usize,
+) -> Result<(), Error>
+where
+    E: RegionEnhancer + ?Sized,
+{
+    let output_width = width * scale;
+
+    for region in enhancer.detect(input, width, height, channels)? {
+        let x = region.x.min(width);
+        let y = region.y.min(height);
+        let crop_width = region.width.min(width - x) * scale;
+        let crop_height = region.height.min(height - y) * scale;
+        if crop_width == 0 || crop_height == 0 {
+            continue;
+        }
+        let (x, y) = (x * scale, y * scale);
+
+        let mut crop = Vec::with_capacity(crop_width * crop_height * channels);
+        for row in y..y + crop_height {
+            let start = (row * output_width + x) * channels;
+            crop.extend_from_slice(&output[start..start + crop_width * channels]);
+        }
+
+        let enhanced = enhancer.enhance(&crop, crop_width, crop_height, channels)?;
+        if enhanced.len() != crop.len() {
+            return Err(Error::InvalidTileOutput {
+                expected_length: crop.len(),
+                actual_length: enhanced.len(),
+            });
+        }
+
+        let feather = (crop_width.min(crop_height) / 8).max(1) as f32;
+        for row in 0..crop_height {
+            for column in 0..crop_width {
+                let edge = row.min(column).min(crop_height - 1 - row).min(crop_width - 1 - column);
+                let weight = ((edge as f32 + 0.5) / feather).min(1.0);
+                let source = (row * crop_width + column) * channels;
+                let target = ((y + row) * output_width + x + column) * channels;
+                for channel in 0..channels {
+                    let base = output[target + channel] as f32;
+                    let value = base + (enhanced[source + channel] as f32 - base) * weight;
+                    output[target + channel] = value.round() as u8;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use crate::Error;
+use crate::{OptionsResampleFilter, TileBackend};
+
+use std::f32::consts::PI;
+
+const fn support(filter: OptionsResampleFilter) -> f32 {
+    match filter {
+        OptionsResampleFilter::Bicubic => 2.0,
+        OptionsResampleFilter::Lanczos => 3.0,
+    }
+}
+
+fn weight(filter: OptionsResampleFilter, x: f32) -> f32 {
+    let x = x.abs();
+    match filter {
+        // Catmull-Rom
+        OptionsResampleFilter::Bicubic if x < 1.0 => 1.5 * x * x * x - 2.5 * x * x + 1.0,
+        OptionsResampleFilter::Bicubic if x < 2.0 => -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0,
+        OptionsResampleFilter::Lanczos if x == 0.0 => 1.0,
+        OptionsResampleFilter::Lanczos if x < 3.0 => 3.0 * (PI * x).sin() * (PI * x / 3.0).sin() / (PI * PI * x * x),
+        _ => 0.0,
+    }
+}
+
+/// Classical interpolation as a `TileBackend`, for blending with or standing in for the network.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resampler {
+    scale: usize,
+    filter: OptionsResampleFilter,
+}
+
+impl Resampler {
+    pub fn new(scale: usize, filter: OptionsResampleFilter) -> Self {
+        Self {
+            scale: scale.max(1),
+            filter,
+        }
+    }
+
+    pub fn upscale(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Vec<u8> {
+        resize(input, width, height, channels, width * self.scale, height * self.scale, self.filter)
+    }
+}
+
+/// Resamples to any size. When shrinking, the kernel is stretched over the source so it also antialiases.
+pub(crate) fn resize(
+    input: &[u8],
+    width: usize,
+    height: usize,
+    channels: usize,
+    output_width: usize,
+    output_height: usize,
+    filter: OptionsResampleFilter,
+) -> Vec<u8> {
+    let columns = contributions(width, output_width, filter);
+    let rows = contributions(height, output_height, filter);
+
+    let mut horizontal = vec![0.0f32; output_width * height * channels];
+    for y in 0..height {
+        for (x, (first, weights)) in columns.iter().enumerate() {
+            let target = (y * output_width + x) * channels;
+            for (offset, weight) in weights.iter().enumerate() {
+                let source = (y * width + first + offset) * channels;
+                for channel in 0..channels {
+                    horizontal[target + channel] += input[source + channel] as f32 * weight;
+                }
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(output_width * output_height * channels);
+    for (first, weights) in &rows {
+        for x in 0..output_width {
+            for channel in 0..channels {
+                let value: f32 = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, weight)| horizontal[((first + offset) * output_width + x) * channels + channel] * weight)
+                    .sum();
+                output.push(value.round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+
+    output
+}
+
+pub(crate) fn resize_nearest(
+    input: &[u8],
+    width: usize,
+    height: usize,
+    channels: usize,
+    output_width: usize,
+    output_height: usize,
+) -> Vec<u8> {
+    let mut output = Vec::with_capacity(output_width * output_height * channels);
+    for y in 0..output_height {
+        let source_y = y * height / output_height;
+        for x in 0..output_width {
+            let offset = (source_y * width + x * width / output_width) * channels;
+            output.extend_from_slice(&input[offset..offset + channels]);
+        }
+    }
+    output
+}
+
+/// For every output coordinate, the first source index it reads and the normalised weights from there.
+/// Taps past the border are folded onto the edge pixel.
+fn contributions(length: usize, output_length: usize, filter: OptionsResampleFilter) -> Vec<(usize, Vec<f32>)> {
+    let ratio = length as f32 / output_length as f32;
+    let stretch = ratio.max(1.0);
+    let support = support(filter) * stretch;
+
+    (0..output_length)
+        .map(|output| {
+            let center = (output as f32 + 0.5) * ratio - 0.5;
+            let start = (center - support).floor() as isize + 1;
+            let end = (center + support).floor() as isize;
+            let first = start.clamp(0, length as isize - 1) as usize;
+            let last = end.clamp(0, length as isize - 1) as usize;
+
+            let mut weights = vec![0.0f32; last - first + 1];
+            for source in start..=end {
+                let index = source.clamp(0, length as isize - 1) as usize;
+                weights[index - first] += weight(filter, (source as f32 - center) / stretch);
+            }
+            let total: f32 = weights.iter().sum();
+            weights.iter_mut().for_each(|weight| *weight /= total);
+            (first, weights)
+        })
+        .collect()
+}
+
+impl TileBackend for Resampler {
+    fn scale_factor(&self) -> usize {
+        self.scale
+    }
+
+    fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        let expected_length = width * height * channels;
+        if input.len() != expected_length {
+            return Err(Error::InvalidInput {
+                expected_length,
+                actual_length: input.len(),
+            });
+        }
+
+        Ok(self.upscale(input, width, height, channels))
+    }
+}
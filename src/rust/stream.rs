@@ -0,0 +1,177 @@
+use crate::Error;
+use crate::RealEsrgan;
+
+use std::sync::mpsc::{self, Receiver};
+
+/// Lazily upscales same-sized buffers. A background thread pulls up to
+/// `lookahead` inputs ahead of the upscaler so producing the next input
+/// overlaps with GPU work on the current one.
+pub struct ProcessIter<'a, B> {
+    realesrgan: &'a RealEsrgan,
+    receiver: Receiver<B>,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, B> ProcessIter<'a, B>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    pub(crate) fn new<I>(realesrgan: &'a RealEsrgan, inputs: I, width: usize, height: usize, lookahead: usize) -> Self
+    where
+        I: IntoIterator<Item = B>,
+        I::IntoIter: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(lookahead);
+        let inputs = inputs.into_iter();
+
+        std::thread::spawn(move || {
+            for input in inputs {
+                if sender.send(input).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            realesrgan,
+            receiver,
+            width,
+            height,
+        }
+    }
+}
+
+impl<B> Iterator for ProcessIter<'_, B>
+where
+    B: AsRef<[u8]>,
+{
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.receiver.recv().ok()?;
+        Some(self.realesrgan.process(input.as_ref(), self.width, self.height))
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::process_stream::ProcessStream;
+
+#[cfg(feature = "async")]
+mod process_stream {
+    use crate::Error;
+    use crate::RealEsrgan;
+
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::sync::{Arc, Condvar, Mutex, PoisonError};
+    use std::task::{Context, Poll, Waker};
+
+    use futures_core::Stream;
+
+    struct State {
+        results: VecDeque<Result<Vec<u8>, Error>>,
+        waker: Option<Waker>,
+        finished: bool,
+        closed: bool,
+    }
+
+    struct Shared {
+        state: Mutex<State>,
+        space: Condvar,
+        lookahead: usize,
+    }
+
+    /// Ends the stream however the worker exits, with a last error if it panicked.
+    struct Finish(Arc<Shared>);
+
+    impl Drop for Finish {
+        fn drop(&mut self) {
+            let mut state = self.0.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if std::thread::panicking() {
+                state.results.push_back(Err(Error::WorkerPanicked));
+            }
+            state.finished = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Upscales on a dedicated thread and yields results as they complete,
+    /// keeping at most `lookahead` finished results buffered.
+    pub struct ProcessStream {
+        shared: Arc<Shared>,
+    }
+
+    impl ProcessStream {
+        pub(crate) fn new<I, B>(realesrgan: RealEsrgan, inputs: I, width: usize, height: usize, lookahead: usize) -> Self
+        where
+            I: IntoIterator<Item = B>,
+            I::IntoIter: Send + 'static,
+            B: AsRef<[u8]>,
+        {
+            let shared = Arc::new(Shared {
+                state: Mutex::new(State {
+                    results: VecDeque::new(),
+                    waker: None,
+                    finished: false,
+                    closed: false,
+                }),
+                space: Condvar::new(),
+                lookahead: lookahead.max(1),
+            });
+
+            let worker = Arc::clone(&shared);
+            let inputs = inputs.into_iter();
+
+            std::thread::spawn(move || {
+                let _finish = Finish(Arc::clone(&worker));
+                for input in inputs {
+                    let result = realesrgan.process(input.as_ref(), width, height);
+
+                    let mut state = worker.state.lock().unwrap();
+                    while state.results.len() >= worker.lookahead && !state.closed {
+                        state = worker.space.wait(state).unwrap();
+                    }
+                    if state.closed {
+                        return;
+                    }
+
+                    state.results.push_back(result);
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                }
+            });
+
+            Self { shared }
+        }
+    }
+
+    impl Stream for ProcessStream {
+        type Item = Result<Vec<u8>, Error>;
+
+        fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(result) = state.results.pop_front() {
+                self.shared.space.notify_one();
+                return Poll::Ready(Some(result));
+            }
+
+            if state.finished {
+                return Poll::Ready(None);
+            }
+
+            state.waker = Some(context.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    impl Drop for ProcessStream {
+        fn drop(&mut self) {
+            self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).closed = true;
+            self.shared.space.notify_one();
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::Error;
+use crate::OptionsTileBlend;
+
+/// Upscales a single tile. The input already carries its context padding.
+pub trait TileBackend {
+    fn scale_factor(&self) -> usize;
+
+    fn process_tile(
+        &self,
+        input: &[u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> Result<Vec<u8>, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tiler {
+    tile_width: usize,
+    tile_height: usize,
+    padding: usize,
+    blend: OptionsTileBlend,
+}
+
+impl Tiler {
+    pub fn new(tile_width: usize, tile_height: usize) -> Self {
+        Self {
+            tile_width: tile_width.max(1),
+            tile_height: tile_height.max(1),
+            padding: 10,
+            blend: OptionsTileBlend::Crop,
+        }
+    }
+
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn blend(mut self, blend: OptionsTileBlend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    fn overlap(&self) -> usize {
+        match self.blend {
+            OptionsTileBlend::Crop => 0,
+            OptionsTileBlend::Linear => self.padding / 2,
+        }
+    }
+
+    pub fn tiles(&self, width: usize, height: usize) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(self.tile_height) {
+            for x in (0..width).step_by(self.tile_width) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: self.tile_width.min(width - x),
+                    height: self.tile_height.min(height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Copies `tile` plus its padding out of the image, reflecting at the image borders.
+    pub fn extract(
+        &self,
+        input: &[u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+        tile: &Tile,
+    ) -> Vec<u8> {
+        let padded_width = tile.width + 2 * self.padding;
+        let padded_height = tile.height + 2 * self.padding;
+        let mut output = Vec::with_capacity(padded_width * padded_height * channels);
+
+        for ty in 0..padded_height {
+            let y = reflect(tile.y as isize + ty as isize - self.padding as isize, height);
+            let row = &input[y * width * channels..(y + 1) * width * channels];
+            for tx in 0..padded_width {
+                let x = reflect(tile.x as isize + tx as isize - self.padding as isize, width);
+                output.extend_from_slice(&row[x * channels..(x + 1) * channels]);
+            }
+        }
+
+        output
+    }
+
+    pub fn process<B>(
+        &self,
+        backend: &B,
+        input: &[u8],
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> Result<Vec<u8>, Error>
+    where
+        B: TileBackend + ?Sized,
+    {
+        let expected_length = width * height * channels;
+        if input.len() != expected_length {
+            return Err(Error::InvalidInput {
+                expected_length,
+                actual_length: input.len(),
+            });
+        }
+
+        let scale = backend.scale_factor();
+        let mut stitcher = Stitcher::new(width * scale, height * scale, channels, self.overlap() * scale);
+
+        for tile in self.tiles(width, height) {
+            let padded = self.extract(input, width, height, channels, &tile);
+            let padded_width = tile.width + 2 * self.padding;
+            let padded_height = tile.height + 2 * self.padding;
+
+            let upscaled = backend.process_tile(&padded, padded_width, padded_height, channels)?;
+            let expected_length = padded_width * scale * padded_height * scale * channels;
+            if upscaled.len() != expected_length {
+                return Err(Error::InvalidTileOutput {
+                    expected_length,
+                    actual_length: upscaled.len(),
+                });
+            }
+
+            stitcher.add(
+                &upscaled,
+                padded_width * scale,
+                Tile {
+                    x: tile.x * scale,
+                    y: tile.y * scale,
+                    width: tile.width * scale,
+                    height: tile.height * scale,
+                },
+                self.padding * scale,
+            );
+        }
+
+        Ok(stitcher.finish())
+    }
+}
+
+fn reflect(coordinate: isize, length: usize) -> usize {
+    let last = length as isize - 1;
+    if last <= 0 {
+        return 0;
+    }
+    let coordinate = coordinate.abs();
+    let coordinate = last - (coordinate - last).abs();
+    coordinate.clamp(0, last) as usize
+}
+
+enum Stitcher {
+    Crop {
+        output: Vec<u8>,
+        width: usize,
+        channels: usize,
+    },
+    Linear {
+        accumulator: Vec<f32>,
+        weights: Vec<f32>,
+        width: usize,
+        height: usize,
+        channels: usize,
+        overlap: usize,
+    },
+}
+
+impl Stitcher {
+    fn new(width: usize, height: usize, channels: usize, overlap: usize) -> Self {
+        if overlap == 0 {
+            Self::Crop {
+                output: vec![0u8; width * height * channels],
+                width,
+                channels,
+            }
+        } else {
+            Self::Linear {
+                accumulator: vec![0f32; width * height * channels],
+                weights: vec![0f32; width * height],
+                width,
+                height,
+                channels,
+                overlap,
+            }
+        }
+    }
+
+    /// `source` is a padded tile of `source_width` pixels per row whose unpadded area lands on `tile`.
+    fn add(&mut self, source: &[u8], source_width: usize, tile: Tile, padding: usize) {
+        match self {
+            Self::Crop { output, width, channels } => {
+                let row_length = tile.width * *channels;
+                for y in 0..tile.height {
+                    let source_offset = ((y + padding) * source_width + padding) * *channels;
+                    let output_offset = ((tile.y + y) * *width + tile.x) * *channels;
+                    output[output_offset..output_offset + row_length]
+                        .copy_from_slice(&source[source_offset..source_offset + row_length]);
+                }
+            }
+            Self::Linear { accumulator, weights, width, height, channels, overlap } => {
+                // extend into the neighbouring tiles and fade across the shared band
+                let overlap = (*overlap).min(padding);
+                let has_left = tile.x > 0;
+                let has_top = tile.y > 0;
+                let has_right = tile.x + tile.width < *width;
+                let has_bottom = tile.y + tile.height < *height;
+
+                let x0 = if has_left { tile.x - overlap } else { tile.x };
+                let y0 = if has_top { tile.y - overlap } else { tile.y };
+                let x1 = if has_right { tile.x + tile.width + overlap } else { tile.x + tile.width };
+                let y1 = if has_bottom { tile.y + tile.height + overlap } else { tile.y + tile.height };
+                let x1 = x1.min(*width);
+                let y1 = y1.min(*height);
+
+                let ramp = (overlap * 2) as f32;
+                let fade = |position: usize, start: usize, end: usize, fade_in: bool, fade_out: bool| {
+                    let mut weight = 1f32;
+                    if fade_in {
+                        weight = weight.min(((position - start) as f32 + 0.5) / ramp);
+                    }
+                    if fade_out {
+                        weight = weight.min(((end - position) as f32 - 0.5) / ramp);
+                    }
+                    weight.min(1.0)
+                };
+
+                for y in y0..y1 {
+                    let weight_y = fade(y, y0, y1, has_top, has_bottom);
+                    let source_y = y + padding - tile.y;
+                    for x in x0..x1 {
+                        let weight = weight_y * fade(x, x0, x1, has_left, has_right);
+                        let source_x = x + padding - tile.x;
+                        let source_offset = (source_y * source_width + source_x) * *channels;
+                        let output_index = y * *width + x;
+                        for c in 0..*channels {
+                            accumulator[output_index * *channels + c] += source[source_offset + c] as f32 * weight;
+                        }
+                        weights[output_index] += weight;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Self::Crop { output, .. } => output,
+            Self::Linear { accumulator, weights, channels, .. } => accumulator
+                .chunks_exact(channels)
+                .zip(weights)
+                .flat_map(|(pixel, weight)| {
+                    let weight = if weight > 0.0 { weight } else { 1.0 };
+                    pixel.iter().map(move |value| (value / weight).round().clamp(0.0, 255.0) as u8)
+                })
+                .collect(),
+        }
+    }
+}
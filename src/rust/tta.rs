@@ -0,0 +1,131 @@
+use crate::Error;
+use crate::{OptionsTtaMerge, OptionsTtaMode, TileBackend};
+
+/// A flip of both axes followed by an optional transpose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transform {
+    flip_x: bool,
+    flip_y: bool,
+    transpose: bool,
+}
+
+impl Transform {
+    const fn new(flip_x: bool, flip_y: bool, transpose: bool) -> Self {
+        Self { flip_x, flip_y, transpose }
+    }
+
+    /// Transposing swaps which axis a flip lands on, so undoing it swaps them back.
+    const fn inverse(&self) -> Self {
+        if self.transpose {
+            Self::new(self.flip_y, self.flip_x, true)
+        } else {
+            *self
+        }
+    }
+
+    fn apply(&self, input: &[u8], width: usize, height: usize, channels: usize) -> (Vec<u8>, usize, usize) {
+        let (output_width, output_height) = if self.transpose { (height, width) } else { (width, height) };
+        let mut output = Vec::with_capacity(input.len());
+
+        for y in 0..output_height {
+            for x in 0..output_width {
+                let (u, v) = if self.transpose { (y, x) } else { (x, y) };
+                let source_x = if self.flip_x { width - 1 - u } else { u };
+                let source_y = if self.flip_y { height - 1 - v } else { v };
+                let offset = (source_y * width + source_x) * channels;
+                output.extend_from_slice(&input[offset..offset + channels]);
+            }
+        }
+
+        (output, output_width, output_height)
+    }
+}
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::new(false, false, false),
+    Transform::new(true, false, false),
+    Transform::new(false, true, false),
+    Transform::new(true, true, false),
+    Transform::new(false, false, true),
+    Transform::new(true, false, true),
+    Transform::new(false, true, true),
+    Transform::new(true, true, true),
+];
+
+/// Test-time augmentation around any backend: every tile is upscaled once per
+/// flip/transpose of the mode, turned back, and the results merged per byte.
+pub struct Tta<'a, B: ?Sized> {
+    backend: &'a B,
+    mode: OptionsTtaMode,
+    merge: OptionsTtaMerge,
+}
+
+impl<'a, B: TileBackend + ?Sized> Tta<'a, B> {
+    pub fn new(backend: &'a B, mode: OptionsTtaMode) -> Self {
+        Self {
+            backend,
+            mode,
+            merge: OptionsTtaMerge::Mean,
+        }
+    }
+
+    pub fn merge(mut self, merge: OptionsTtaMerge) -> Self {
+        self.merge = merge;
+        self
+    }
+}
+
+impl<B: TileBackend + ?Sized> TileBackend for Tta<'_, B> {
+    fn scale_factor(&self) -> usize {
+        self.backend.scale_factor()
+    }
+
+    fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        let transforms = &TRANSFORMS[..self.mode.passes()];
+        if transforms.len() <= 1 {
+            return self.backend.process_tile(input, width, height, channels);
+        }
+
+        let scale = self.scale_factor();
+        let expected_length = width * scale * height * scale * channels;
+        let mut outputs = Vec::with_capacity(transforms.len());
+
+        for transform in transforms {
+            let (transformed, transformed_width, transformed_height) = transform.apply(input, width, height, channels);
+            let upscaled = self.backend.process_tile(&transformed, transformed_width, transformed_height, channels)?;
+            if upscaled.len() != expected_length {
+                return Err(Error::InvalidTileOutput {
+                    expected_length,
+                    actual_length: upscaled.len(),
+                });
+            }
+            let (restored, _, _) = transform.inverse().apply(&upscaled, transformed_width * scale, transformed_height * scale, channels);
+            outputs.push(restored);
+        }
+
+        Ok(merge(&outputs, self.merge))
+    }
+}
+
+fn merge(outputs: &[Vec<u8>], merge: OptionsTtaMerge) -> Vec<u8> {
+    let count = outputs.len();
+    let mut values = vec![0u8; count];
+
+    (0..outputs[0].len())
+        .map(|index| match merge {
+            OptionsTtaMerge::Mean => {
+                let total: usize = outputs.iter().map(|output| output[index] as usize).sum();
+                ((total + count / 2) / count) as u8
+            }
+            OptionsTtaMerge::Median => {
+                for (value, output) in values.iter_mut().zip(outputs) {
+                    *value = output[index];
+                }
+                values.sort_unstable();
+                let upper = values[count / 2] as usize;
+                let lower = values[(count - 1) / 2] as usize;
+                (upper + lower).div_ceil(2) as u8
+            }
+        })
+        .collect()
+}
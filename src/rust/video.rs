@@ -0,0 +1,304 @@
+use crate::Error;
+use crate::RealEsrgan;
+use crate::FrameDeduplicator;
+
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStderr, Command, Stdio};
+use std::thread::JoinHandle;
+
+/// The first video stream as the decoder hands it over: upright, at a constant frame rate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoInfo {
+    pub width: usize,
+    pub height: usize,
+    /// The average rate, which variable-rate sources are resampled to.
+    pub frame_rate: String,
+    /// The duration at `frame_rate`, which is how many frames the decoder hands over.
+    pub frames: Option<u64>,
+    /// Degrees the frames are turned by to display upright, already applied to `width` and `height`.
+    pub rotation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoProgress {
+    pub frame: u64,
+    pub total_frames: Option<u64>,
+    /// The frame repeated the previous upscaled output instead of being upscaled.
+    pub duplicate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VideoReport {
+    pub frames: u64,
+    pub skipped: u64,
+}
+
+/// Upscales a video by piping raw RGB frames through a local `ffmpeg`.
+/// Audio and subtitle streams are copied from the input unchanged.
+pub struct VideoUpscaler<'a> {
+    realesrgan: &'a RealEsrgan,
+    ffmpeg: OsString,
+    ffprobe: OsString,
+    codec: String,
+    pixel_format: String,
+    encoder_args: Vec<String>,
+    duplicate_threshold: Option<f32>,
+    progress: Option<Box<dyn FnMut(VideoProgress) + 'a>>,
+}
+
+impl<'a> VideoUpscaler<'a> {
+    pub fn new(realesrgan: &'a RealEsrgan) -> Self {
+        Self {
+            realesrgan,
+            ffmpeg: "ffmpeg".into(),
+            ffprobe: "ffprobe".into(),
+            codec: "libx264".to_string(),
+            pixel_format: "yuv420p".to_string(),
+            encoder_args: Vec::new(),
+            duplicate_threshold: None,
+            progress: None,
+        }
+    }
+
+    pub fn ffmpeg<P: AsRef<Path>>(mut self, ffmpeg: P) -> Self {
+        self.ffmpeg = ffmpeg.as_ref().into();
+        self
+    }
+
+    pub fn ffprobe<P: AsRef<Path>>(mut self, ffprobe: P) -> Self {
+        self.ffprobe = ffprobe.as_ref().into();
+        self
+    }
+
+    pub fn codec<S: Into<String>>(mut self, codec: S) -> Self {
+        self.codec = codec.into();
+        self
+    }
+
+    pub fn pixel_format<S: Into<String>>(mut self, pixel_format: S) -> Self {
+        self.pixel_format = pixel_format.into();
+        self
+    }
+
+    /// Extra encoder arguments placed before the output path, e.g. `["-crf", "18"]`.
+    pub fn encoder_args<I, S>(mut self, encoder_args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.encoder_args = encoder_args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Reuse the previous upscaled frame when the mean absolute difference to its
+    /// source is at most `threshold` (0 to 255). Off by default.
+    pub fn skip_duplicates(mut self, threshold: f32) -> Self {
+        self.duplicate_threshold = Some(threshold);
+        self
+    }
+
+    pub fn progress<F>(mut self, progress: F) -> Self
+    where
+        F: FnMut(VideoProgress) + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn probe<P: AsRef<Path>>(&self, input: P) -> Result<VideoInfo, Error> {
+        let output = Command::new(&self.ffprobe)
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height,avg_frame_rate,r_frame_rate,duration:stream_side_data=rotation:stream_tags=rotate:format=duration"])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .arg(input.as_ref())
+            .output()
+            .map_err(|error| Error::VideoProbeFailed(error.to_string()))?;
+
+        if !output.status.success() {
+            return Err(Error::VideoProbeFailed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let info = parse_probe(&stdout).ok_or_else(|| Error::VideoProbeFailed(format!("unexpected ffprobe output: {stdout}")))?;
+        if info.rotation % 90 != 0 {
+            return Err(Error::VideoProbeFailed(format!("unsupported rotation of {} degrees", info.rotation)));
+        }
+        Ok(info)
+    }
+
+    pub fn run<P, Q>(&mut self, input: P, output: Q) -> Result<VideoReport, Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let input = input.as_ref();
+        let info = self.probe(input)?;
+        let scale = self.realesrgan.output_scale();
+
+        let mut decoder = Command::new(&self.ffmpeg)
+            .args(["-v", "error", "-nostdin", "-i"])
+            .arg(input)
+            // the raw pipe carries no timestamps, so variable-rate sources are resampled to the rate the encoder assumes
+            .args(["-map", "0:v:0", "-fps_mode", "cfr", "-r", &info.frame_rate])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| Error::FfmpegFailed(error.to_string()))?;
+        let decoder_errors = collect_stderr(decoder.stderr.take());
+
+        let mut encoder = Command::new(&self.ffmpeg)
+            .args(["-v", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", info.width * scale, info.height * scale)])
+            .args(["-framerate", &info.frame_rate, "-i", "-", "-i"])
+            .arg(input)
+            .args(["-map", "0:v:0", "-map", "1:a?", "-map", "1:s?", "-c:a", "copy", "-c:s", "copy"])
+            .args(["-c:v", &self.codec, "-pix_fmt", &self.pixel_format])
+            .args(&self.encoder_args)
+            .arg(output.as_ref())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| Error::FfmpegFailed(error.to_string()))?;
+        let encoder_errors = collect_stderr(encoder.stderr.take());
+
+        let result = self.pipe_frames(&info, &mut decoder, &mut encoder);
+
+        // closing stdin lets the encoder flush and exit
+        drop(encoder.stdin.take());
+        if result.is_err() {
+            let _ = decoder.kill();
+        }
+
+        let decoder_status = wait(&mut decoder, decoder_errors);
+        let encoder_status = wait(&mut encoder, encoder_errors);
+
+        match (result, decoder_status, encoder_status) {
+            (Ok(report), Ok(()), Ok(())) => Ok(report),
+            // a broken pipe only means the encoder died, its own message says why
+            (Err(Error::FfmpegFailed(_)), _, Err(error)) => Err(error),
+            (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => Err(error),
+        }
+    }
+
+    fn pipe_frames(&mut self, info: &VideoInfo, decoder: &mut Child, encoder: &mut Child) -> Result<VideoReport, Error> {
+        let mut frames_in = decoder.stdout.take().ok_or_else(|| Error::FfmpegFailed("decoder has no stdout".to_string()))?;
+        let mut frames_out = encoder.stdin.take().ok_or_else(|| Error::FfmpegFailed("encoder has no stdin".to_string()))?;
+
+        let mut frame = vec![0u8; info.width * info.height * 3];
+        let mut count = 0;
+        let mut deduplicator = FrameDeduplicator::new(self.duplicate_threshold);
+
+        while read_frame(&mut frames_in, &mut frame)? {
+            let (upscaled, duplicate) = deduplicator.process(&frame, |frame| self.realesrgan.process(frame, info.width, info.height))?;
+            frames_out
+                .write_all(upscaled)
+                .map_err(|error| Error::FfmpegFailed(format!("failed to write frame {count}: {error}")))?;
+
+            count += 1;
+            if let Some(progress) = self.progress.as_mut() {
+                progress(VideoProgress {
+                    frame: count,
+                    total_frames: info.frames,
+                    duplicate,
+                });
+            }
+        }
+
+        Ok(VideoReport {
+            frames: count,
+            skipped: deduplicator.skipped(),
+        })
+    }
+}
+
+/// Reads the `key=value` lines of ffprobe. The rotation comes from the display matrix,
+/// or the `rotate` tag of older muxers; ffmpeg applies it while decoding. The stream
+/// duration comes before the container's, which stands in when the stream has none.
+fn parse_probe(output: &str) -> Option<VideoInfo> {
+    let (mut width, mut height, mut duration) = (None, None, None);
+    let (mut average_rate, mut real_rate) = (None, None);
+    let mut rotation = 0.0f64;
+
+    for (key, value) in output.lines().filter_map(|line| line.split_once('=')) {
+        let value = value.trim();
+        match key.trim() {
+            "width" => width = value.parse::<usize>().ok(),
+            "height" => height = value.parse::<usize>().ok(),
+            "avg_frame_rate" => average_rate = Some(value.to_string()),
+            "r_frame_rate" => real_rate = Some(value.to_string()),
+            "duration" => duration = duration.or_else(|| value.parse::<f64>().ok()),
+            "rotation" | "TAG:rotate" => rotation = value.parse().ok()?,
+            _ => {}
+        }
+    }
+
+    // still images and some raw streams have no average
+    let frame_rate = average_rate.filter(|rate| !rate.starts_with('0')).or(real_rate)?;
+    let frames = duration
+        .zip(parse_rate(&frame_rate))
+        .map(|(duration, rate)| (duration * rate).round() as u64);
+    let rotation = (rotation.round() as i64).rem_euclid(360) as u32;
+    let (width, height) = match rotation {
+        90 | 270 => (height?, width?),
+        _ => (width?, height?),
+    };
+
+    Some(VideoInfo {
+        width,
+        height,
+        frame_rate,
+        frames,
+        rotation,
+    })
+}
+
+/// A rate like `30000/1001` or `25`.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (numerator, denominator) = rate.split_once('/').unwrap_or((rate, "1"));
+    let (numerator, denominator) = (numerator.parse::<f64>().ok()?, denominator.parse::<f64>().ok()?);
+    (denominator > 0.0).then_some(numerator / denominator)
+}
+
+/// Fills `frame` from the pipe. Returns `false` on a clean end of stream.
+fn read_frame<R: Read>(reader: &mut R, frame: &mut [u8]) -> Result<bool, Error> {
+    let mut filled = 0;
+    while filled < frame.len() {
+        match reader.read(&mut frame[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(Error::FfmpegFailed("truncated frame from decoder".to_string())),
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(Error::FfmpegFailed(error.to_string())),
+        }
+    }
+    Ok(true)
+}
+
+fn collect_stderr(stderr: Option<ChildStderr>) -> Option<JoinHandle<String>> {
+    stderr.map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        })
+    })
+}
+
+fn wait(child: &mut Child, errors: Option<JoinHandle<String>>) -> Result<(), Error> {
+    let status = child.wait().map_err(|error| Error::FfmpegFailed(error.to_string()))?;
+    let message = errors.and_then(|errors| errors.join().ok()).unwrap_or_default();
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::FfmpegFailed(format!("ffmpeg exited with {status}: {}", message.trim())))
+    }
+}
+
+#[cfg(test)]
+mod tests;
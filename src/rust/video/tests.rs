@@ -0,0 +1,20 @@
+use super::*;
+
+#[test]
+fn total_frames_follow_the_forced_rate() {
+    let output = "width=1920\nheight=1080\nr_frame_rate=60/1\navg_frame_rate=24000/1001\nduration=1421.420000\n";
+    let info = parse_probe(output).unwrap();
+    assert_eq!(info.frame_rate, "24000/1001");
+    assert_eq!(info.frames, Some(34080));
+}
+
+#[test]
+fn container_duration_stands_in() {
+    let output = "width=32\nheight=24\nr_frame_rate=5/1\navg_frame_rate=5/1\nduration=N/A\nrotation=-90\nduration=1.200000\n";
+    let info = parse_probe(output).unwrap();
+    assert_eq!((info.width, info.height, info.rotation), (24, 32, 270));
+    assert_eq!(info.frames, Some(6));
+
+    let info = parse_probe("width=32\nheight=24\navg_frame_rate=0/0\nr_frame_rate=25/1\n").unwrap();
+    assert_eq!((info.frame_rate.as_str(), info.frames), ("25/1", None));
+}
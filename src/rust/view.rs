@@ -0,0 +1,57 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    Bgr,
+    Bgra,
+    /// Color already multiplied by alpha, as compositors usually hand it over.
+    GrayAlphaPremultiplied,
+    RgbaPremultiplied,
+    BgraPremultiplied,
+}
+
+impl PixelFormat {
+    pub const fn channels(&self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::GrayAlpha | Self::GrayAlphaPremultiplied => 2,
+            Self::Rgb | Self::Bgr => 3,
+            Self::Rgba | Self::Bgra | Self::RgbaPremultiplied | Self::BgraPremultiplied => 4,
+        }
+    }
+
+    /// Blue first, as OpenCV and many capture APIs store it.
+    pub const fn is_bgr(&self) -> bool {
+        matches!(self, Self::Bgr | Self::Bgra | Self::BgraPremultiplied)
+    }
+
+    pub const fn is_premultiplied(&self) -> bool {
+        matches!(self, Self::GrayAlphaPremultiplied | Self::RgbaPremultiplied | Self::BgraPremultiplied)
+    }
+}
+
+/// Borrowed interleaved pixels together with their dimensions and layout.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a> {
+    pub data: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+}
+
+impl<'a> ImageView<'a> {
+    pub fn new(data: &'a [u8], width: usize, height: usize, format: PixelFormat) -> Self {
+        Self {
+            data,
+            width,
+            height,
+            format,
+        }
+    }
+
+    pub fn expected_length(&self) -> usize {
+        self.width * self.height * self.format.channels()
+    }
+}
@@ -0,0 +1,244 @@
+use crate::Error;
+use crate::{ChromaSampling, ColorRange, YuvFrame};
+
+use std::io::{ErrorKind, Read, Write};
+
+const SIGNATURE: &str = "YUV4MPEG2";
+const MAX_LINE_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Y4mHeader {
+    pub width: usize,
+    pub height: usize,
+    pub frame_rate: (u32, u32),
+    pub sampling: ChromaSampling,
+    /// The `C` tag as read, e.g. `420mpeg2`, written back as long as it still matches `sampling`
+    /// so the chroma siting is kept.
+    pub colorspace: Option<String>,
+    /// From ffmpeg's `XCOLORRANGE` extension, when present.
+    pub range: Option<ColorRange>,
+    /// Parameters passed through unchanged, such as interlacing and pixel aspect ratio.
+    pub parameters: Vec<String>,
+}
+
+impl Y4mHeader {
+    pub fn new(width: usize, height: usize, frame_rate: (u32, u32), sampling: ChromaSampling) -> Self {
+        Self {
+            width,
+            height,
+            frame_rate,
+            sampling,
+            colorspace: None,
+            range: None,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// The same stream with every dimension multiplied by `scale`.
+    pub fn scaled(&self, scale: usize) -> Self {
+        Self {
+            width: self.width * scale,
+            height: self.height * scale,
+            ..self.clone()
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self, Error> {
+        let mut tokens = line.split_ascii_whitespace();
+        if tokens.next() != Some(SIGNATURE) {
+            return Err(Error::Y4mParseFailed("missing YUV4MPEG2 signature".to_string()));
+        }
+
+        let mut width = None;
+        let mut height = None;
+        let mut frame_rate = None;
+        let mut sampling = ChromaSampling::Yuv420;
+        let mut colorspace = None;
+        let mut range = None;
+        let mut parameters = Vec::new();
+
+        for token in tokens {
+            let invalid = || Error::Y4mParseFailed(format!("invalid header parameter: {token}"));
+            let mut chars = token.chars();
+            let tag = chars.next();
+            let value = chars.as_str();
+            match tag {
+                Some('W') => width = Some(value.parse().map_err(|_| invalid())?),
+                Some('H') => height = Some(value.parse().map_err(|_| invalid())?),
+                Some('F') => frame_rate = Some(parse_ratio(value).ok_or_else(invalid)?),
+                Some('C') => {
+                    sampling = parse_sampling(value).ok_or_else(|| Error::Y4mParseFailed(format!("unsupported colorspace: {value}")))?;
+                    colorspace = Some(value.to_string());
+                }
+                Some('X') if value.starts_with("COLORRANGE=") => {
+                    range = match &value["COLORRANGE=".len()..] {
+                        "FULL" => Some(ColorRange::Full),
+                        "LIMITED" => Some(ColorRange::Limited),
+                        _ => None,
+                    }
+                }
+                _ => parameters.push(token.to_string()),
+            }
+        }
+
+        let width = width.filter(|width| *width > 0).ok_or_else(|| Error::Y4mParseFailed("missing width".to_string()))?;
+        let height = height.filter(|height| *height > 0).ok_or_else(|| Error::Y4mParseFailed("missing height".to_string()))?;
+        let frame_rate = frame_rate.ok_or_else(|| Error::Y4mParseFailed("missing frame rate".to_string()))?;
+
+        Ok(Self {
+            width,
+            height,
+            frame_rate,
+            sampling,
+            colorspace,
+            range,
+            parameters,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let colorspace = match (&self.colorspace, self.sampling) {
+            (Some(colorspace), sampling) if parse_sampling(colorspace) == Some(sampling) => colorspace,
+            (_, ChromaSampling::Yuv420) => "420jpeg",
+            (_, ChromaSampling::Yuv444) => "444",
+        };
+
+        let mut line = format!(
+            "{SIGNATURE} W{} H{} F{}:{} C{colorspace}",
+            self.width, self.height, self.frame_rate.0, self.frame_rate.1
+        );
+        for parameter in &self.parameters {
+            line.push(' ');
+            line.push_str(parameter);
+        }
+        match self.range {
+            Some(ColorRange::Full) => line.push_str(" XCOLORRANGE=FULL"),
+            Some(ColorRange::Limited) => line.push_str(" XCOLORRANGE=LIMITED"),
+            None => {}
+        }
+        line.push('\n');
+        line
+    }
+}
+
+/// Reads frames from a YUV4MPEG2 stream such as `ffmpeg -f yuv4mpegpipe` output.
+pub struct Y4mReader<R> {
+    reader: R,
+    header: Y4mHeader,
+}
+
+impl<R: Read> Y4mReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let line = read_line(&mut reader)?.ok_or_else(|| Error::Y4mParseFailed("empty stream".to_string()))?;
+        let header = Y4mHeader::parse(&line)?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Returns `None` once the stream ends.
+    pub fn read_frame(&mut self) -> Result<Option<YuvFrame>, Error> {
+        let Some(line) = read_line(&mut self.reader)? else {
+            return Ok(None);
+        };
+        if line.split_ascii_whitespace().next() != Some("FRAME") {
+            return Err(Error::Y4mParseFailed(format!("expected FRAME, got {line}")));
+        }
+
+        let mut data = vec![0u8; self.header.sampling.frame_length(self.header.width, self.header.height)];
+        self.reader.read_exact(&mut data).map_err(|error| Error::Y4mIoFailed(error.to_string()))?;
+
+        Ok(Some(YuvFrame::new(data, self.header.width, self.header.height, self.header.sampling)))
+    }
+}
+
+impl<R: Read> Iterator for Y4mReader<R> {
+    type Item = Result<YuvFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Writes frames as a YUV4MPEG2 stream, e.g. for `ffmpeg -f yuv4mpegpipe -i -`.
+pub struct Y4mWriter<W> {
+    writer: W,
+    header: Y4mHeader,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut writer: W, header: Y4mHeader) -> Result<Self, Error> {
+        writer
+            .write_all(header.to_line().as_bytes())
+            .map_err(|error| Error::Y4mIoFailed(error.to_string()))?;
+        Ok(Self { writer, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    pub fn write_frame(&mut self, frame: &YuvFrame) -> Result<(), Error> {
+        let expected_length = self.header.sampling.frame_length(self.header.width, self.header.height);
+        if frame.width != self.header.width
+            || frame.height != self.header.height
+            || frame.sampling != self.header.sampling
+            || frame.data.len() != expected_length
+        {
+            return Err(Error::InvalidInput {
+                expected_length,
+                actual_length: frame.data.len(),
+            });
+        }
+
+        self.writer
+            .write_all(b"FRAME\n")
+            .and_then(|()| self.writer.write_all(&frame.data))
+            .map_err(|error| Error::Y4mIoFailed(error.to_string()))
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(|error| Error::Y4mIoFailed(error.to_string()))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn parse_ratio(value: &str) -> Option<(u32, u32)> {
+    let (numerator, denominator) = value.split_once(':')?;
+    Some((numerator.parse().ok()?, denominator.parse().ok()?))
+}
+
+fn parse_sampling(value: &str) -> Option<ChromaSampling> {
+    match value {
+        "420" | "420jpeg" | "420paldv" | "420mpeg2" => Some(ChromaSampling::Yuv420),
+        "444" => Some(ChromaSampling::Yuv444),
+        _ => None,
+    }
+}
+
+/// Reads one byte at a time so nothing past the newline is consumed. Returns `None` at a clean end of stream.
+fn read_line<R: Read>(reader: &mut R) -> Result<Option<String>, Error> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) if line.is_empty() => return Ok(None),
+            Ok(0) => return Err(Error::Y4mParseFailed("unexpected end of stream".to_string())),
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) if line.len() >= MAX_LINE_LENGTH => return Err(Error::Y4mParseFailed("header line too long".to_string())),
+            Ok(_) => line.push(byte[0]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(Error::Y4mIoFailed(error.to_string())),
+        }
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| Error::Y4mParseFailed("header is not valid text".to_string()))
+}
@@ -0,0 +1,174 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSampling {
+    Yuv420,
+    Yuv444,
+}
+
+impl ChromaSampling {
+    pub const fn plane_dimensions(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Self::Yuv420 => (width.div_ceil(2), height.div_ceil(2)),
+            Self::Yuv444 => (width, height),
+        }
+    }
+
+    pub const fn frame_length(&self, width: usize, height: usize) -> usize {
+        let (chroma_width, chroma_height) = self.plane_dimensions(width, height);
+        width * height + 2 * chroma_width * chroma_height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMatrix {
+    #[default]
+    Bt601,
+    Bt709,
+}
+
+impl ColorMatrix {
+    /// Luma weights of red and blue.
+    const fn coefficients(&self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorRange {
+    /// Luma in 16..=235 and chroma in 16..=240, as most video is stored.
+    #[default]
+    Limited,
+    Full,
+}
+
+/// A planar 8-bit YUV frame: the Y plane followed by the U and V planes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YuvFrame {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub sampling: ChromaSampling,
+}
+
+impl YuvFrame {
+    pub fn new(data: Vec<u8>, width: usize, height: usize, sampling: ChromaSampling) -> Self {
+        Self {
+            data,
+            width,
+            height,
+            sampling,
+        }
+    }
+
+    pub fn expected_length(&self) -> usize {
+        self.sampling.frame_length(self.width, self.height)
+    }
+
+    pub fn planes(&self) -> (&[u8], &[u8], &[u8]) {
+        let luma = self.width * self.height;
+        let (chroma_width, chroma_height) = self.sampling.plane_dimensions(self.width, self.height);
+        let chroma = chroma_width * chroma_height;
+        let (y, rest) = self.data.split_at(luma);
+        let (u, v) = rest.split_at(chroma);
+        (y, u, &v[..chroma])
+    }
+
+    /// Converts RGB24 to YUV. 4:2:0 chroma is the average of each 2x2 block.
+    pub fn from_rgb(rgb: &[u8], width: usize, height: usize, sampling: ChromaSampling, matrix: ColorMatrix, range: ColorRange) -> Self {
+        let (kr, kb) = matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (chroma_width, chroma_height) = sampling.plane_dimensions(width, height);
+
+        let mut y_plane = Vec::with_capacity(width * height);
+        let mut u_sum = vec![0.0f32; chroma_width * chroma_height];
+        let mut v_sum = vec![0.0f32; chroma_width * chroma_height];
+        let mut count = vec![0u8; chroma_width * chroma_height];
+
+        for row in 0..height {
+            for column in 0..width {
+                let offset = (row * width + column) * 3;
+                let (r, g, b) = (rgb[offset] as f32, rgb[offset + 1] as f32, rgb[offset + 2] as f32);
+                let luma = kr * r + kg * g + kb * b;
+                let u = (b - luma) / (2.0 * (1.0 - kb));
+                let v = (r - luma) / (2.0 * (1.0 - kr));
+                y_plane.push(encode_luma(luma, range));
+
+                let index = match sampling {
+                    ChromaSampling::Yuv420 => (row / 2) * chroma_width + column / 2,
+                    ChromaSampling::Yuv444 => row * width + column,
+                };
+                u_sum[index] += u;
+                v_sum[index] += v;
+                count[index] += 1;
+            }
+        }
+
+        let mut data = y_plane;
+        data.extend(u_sum.iter().zip(&count).map(|(u, count)| encode_chroma(u / *count as f32, range)));
+        data.extend(v_sum.iter().zip(&count).map(|(v, count)| encode_chroma(v / *count as f32, range)));
+
+        Self::new(data, width, height, sampling)
+    }
+
+    /// Converts to RGB24. 4:2:0 chroma is replicated over each 2x2 block.
+    pub fn to_rgb(&self, matrix: ColorMatrix, range: ColorRange) -> Vec<u8> {
+        let (kr, kb) = matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (chroma_width, _) = self.sampling.plane_dimensions(self.width, self.height);
+        let (y_plane, u_plane, v_plane) = self.planes();
+
+        let mut rgb = Vec::with_capacity(self.width * self.height * 3);
+        for row in 0..self.height {
+            for column in 0..self.width {
+                let index = match self.sampling {
+                    ChromaSampling::Yuv420 => (row / 2) * chroma_width + column / 2,
+                    ChromaSampling::Yuv444 => row * self.width + column,
+                };
+                let luma = decode_luma(y_plane[row * self.width + column], range);
+                let u = decode_chroma(u_plane[index], range);
+                let v = decode_chroma(v_plane[index], range);
+
+                let r = luma + 2.0 * (1.0 - kr) * v;
+                let b = luma + 2.0 * (1.0 - kb) * u;
+                let g = (luma - kr * r - kb * b) / kg;
+                rgb.extend([clamp(r), clamp(g), clamp(b)]);
+            }
+        }
+
+        rgb
+    }
+}
+
+fn clamp(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn encode_luma(luma: f32, range: ColorRange) -> u8 {
+    match range {
+        ColorRange::Limited => clamp(16.0 + luma * 219.0 / 255.0),
+        ColorRange::Full => clamp(luma),
+    }
+}
+
+fn encode_chroma(chroma: f32, range: ColorRange) -> u8 {
+    match range {
+        ColorRange::Limited => clamp(128.0 + chroma * 224.0 / 255.0),
+        ColorRange::Full => clamp(128.0 + chroma),
+    }
+}
+
+fn decode_luma(luma: u8, range: ColorRange) -> f32 {
+    match range {
+        ColorRange::Limited => (luma as f32 - 16.0) * 255.0 / 219.0,
+        ColorRange::Full => luma as f32,
+    }
+}
+
+fn decode_chroma(chroma: u8, range: ColorRange) -> f32 {
+    match range {
+        ColorRange::Limited => (chroma as f32 - 128.0) * 255.0 / 224.0,
+        ColorRange::Full => chroma as f32 - 128.0,
+    }
+}
@@ -0,0 +1,67 @@
+use std::num::NonZeroU32;
+
+use image::metadata::LoopCount;
+use image::{Delay, Frame, ImageFormat, Rgba, RgbaImage};
+use realesrgan_rs::Animation;
+
+fn animation() -> Animation {
+    let frames = [40, 80, 120]
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let buffer = RgbaImage::from_pixel(8, 6, Rgba([value, 255 - value, 0, 255]));
+            Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(100 * (index as u32 + 1), 1))
+        })
+        .collect();
+
+    Animation {
+        frames,
+        loop_count: LoopCount::Finite(NonZeroU32::new(3).unwrap()),
+    }
+}
+
+#[test]
+fn round_trips_gif_and_apng() {
+    let root = std::env::temp_dir().join("realesrgan_rs_animation");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+
+    for name in ["animation.gif", "animation.png"] {
+        let path = root.join(name);
+        animation().save(&path).expect("Failed to save animation");
+
+        let decoded = Animation::open(&path).unwrap().expect("Animation was decoded as a still image");
+        assert_eq!(decoded.frames.len(), 3, "{name}");
+        assert!(matches!(decoded.loop_count, LoopCount::Finite(count) if count.get() == 3), "{name}");
+
+        let delays: Vec<_> = decoded.frames.iter().map(|frame| frame.delay().numer_denom_ms()).collect();
+        let delays: Vec<_> = delays.iter().map(|(numerator, denominator)| numerator / denominator).collect();
+        assert_eq!(delays, vec![100, 200, 300], "{name}");
+    }
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn still_images_are_not_animations() {
+    let path = std::env::temp_dir().join("realesrgan_rs_still.png");
+    RgbaImage::new(4, 4).save(&path).unwrap();
+    assert!(Animation::open(&path).unwrap().is_none());
+    let _ = std::fs::remove_file(&path);
+
+    let result = animation().save_with_format(std::env::temp_dir().join("realesrgan_rs_animation.webp"), ImageFormat::WebP);
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn process_animation() {
+    use realesrgan_rs::{Options, OptionsModel, RealEsrgan};
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let upscaled = realesrgan.process_animation(&animation()).expect("Failed to upscale animation");
+
+    assert_eq!(upscaled.frames.len(), 3);
+    assert_eq!(upscaled.frames[0].buffer().dimensions(), (16, 12));
+    assert_eq!(upscaled.frames[2].delay(), animation().frames[2].delay());
+}
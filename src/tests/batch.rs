@@ -0,0 +1,28 @@
+use realesrgan_rs::{BatchProcessor, RealEsrgan, Options, OptionsModel};
+
+use super::IMAGE;
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn batch_directory() {
+    let root = std::env::temp_dir().join("realesrgan_rs_batch");
+    let _ = std::fs::remove_dir_all(&root);
+    let input = root.join("input");
+    let output = root.join("output");
+    std::fs::create_dir_all(input.join("nested")).unwrap();
+    std::fs::copy(IMAGE, input.join("nested/image.jpg")).unwrap();
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let mut batch = BatchProcessor::new(realesrgan).skip_existing(true);
+
+    let report = batch.run(&input, &output).expect("Failed to run batch");
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert_eq!(report.processed.len(), 1);
+    assert!(output.join("nested/image.png").exists(), "Relative path was not preserved");
+
+    let report = batch.run(&input, &output).expect("Failed to resume batch");
+    assert!(report.processed.is_empty());
+    assert_eq!(report.skipped.len(), 1);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
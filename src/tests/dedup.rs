@@ -0,0 +1,41 @@
+use realesrgan_rs::{frame_difference, FrameDeduplicator};
+
+#[test]
+fn difference_metric() {
+    assert_eq!(frame_difference(&[10, 20, 30, 40], &[10, 20, 30, 40]), 0.0);
+    assert_eq!(frame_difference(&[0, 0, 0, 0], &[4, 0, 0, 4]), 2.0);
+    assert_eq!(frame_difference(&[0, 0], &[0, 0, 0]), f32::MAX);
+}
+
+#[test]
+fn skips_near_duplicates() {
+    let frames: [&[u8]; 5] = [&[10, 10], &[10, 11], &[10, 12], &[10, 13], &[90, 90]];
+    let mut deduplicator = FrameDeduplicator::new(Some(1.0));
+    let mut upscaled = 0;
+    let mut outputs = Vec::new();
+
+    for frame in frames {
+        let (output, _) = deduplicator
+            .process(frame, |frame| {
+                upscaled += 1;
+                Ok(frame.to_vec())
+            })
+            .unwrap();
+        outputs.push(output.clone());
+    }
+
+    // frame 2 differs by 1.0 from frame 0, but frame 3 is compared against frame 0 too
+    assert_eq!(outputs, vec![vec![10, 10], vec![10, 10], vec![10, 10], vec![10, 13], vec![90, 90]]);
+    assert_eq!(upscaled, 3);
+    assert_eq!(deduplicator.skipped(), 2);
+}
+
+#[test]
+fn disabled_never_skips() {
+    let mut deduplicator = FrameDeduplicator::new(None);
+    for _ in 0..3 {
+        let (_, duplicate) = deduplicator.process(&[1, 2, 3], |frame| Ok(frame.len())).unwrap();
+        assert!(!duplicate);
+    }
+    assert_eq!(deduplicator.skipped(), 0);
+}
@@ -1,3 +1,19 @@
+mod tiler;
+mod options;
+mod tta;
+mod resample;
+mod region;
+mod y4m;
+mod dedup;
+#[cfg(feature = "image")]
+mod batch;
+#[cfg(feature = "image")]
+mod animation;
+#[cfg(feature = "image")]
+mod metadata;
+#[cfg(feature = "video")]
+mod video;
+
 use std::path::Path;
 use realesrgan_rs::{RealEsrgan, Options, OptionsModel};
 
@@ -51,4 +67,319 @@ fn from_image() {
     );
     let _ = std::fs::remove_file(upscaled_save_path);
 
-}
\ No newline at end of file
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn mixed_batch() {
+    use realesrgan_rs::{ImageView, PixelFormat};
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+
+    let rgb = vec![128u8; 16 * 8 * 3];
+    let rgba = vec![64u8; 9 * 13 * 4];
+    let truncated = vec![0u8; 10];
+
+    let results = realesrgan.process_views([
+        ImageView::new(&rgb, 16, 8, PixelFormat::Rgb),
+        ImageView::new(&truncated, 4, 4, PixelFormat::Rgb),
+        ImageView::new(&rgba, 9, 13, PixelFormat::Rgba),
+    ]);
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().len(), 32 * 16 * 3);
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap().len(), 18 * 26 * 4);
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn streaming_iter() {
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+
+    let frames = (0..4u8).map(|value| vec![value * 60; 12 * 10 * 3]);
+    let mut count = 0;
+    for result in realesrgan.process_iter(frames, 12, 10, 2) {
+        assert_eq!(result.expect("Failed to upscale frame").len(), 24 * 20 * 3);
+        count += 1;
+    }
+    assert_eq!(count, 4);
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+#[cfg(feature = "async")]
+fn stream_ends_when_worker_panics() {
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+    use futures_core::Stream;
+    use realesrgan_rs::Error;
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let inputs = (0..3).map(|index| {
+        assert!(index < 1, "input source failed");
+        vec![0u8; 8 * 8 * 3]
+    });
+
+    let mut stream = pin!(realesrgan.process_stream(inputs, 8, 8, 2));
+    let mut context = Context::from_waker(Waker::noop());
+    let mut results = Vec::new();
+    loop {
+        match stream.as_mut().poll_next(&mut context) {
+            Poll::Ready(Some(result)) => results.push(result),
+            Poll::Ready(None) => break,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(Error::WorkerPanicked)));
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn chained_pipeline() {
+    use realesrgan_rs::{Error, Pipeline};
+
+    let x2 = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let x4 = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x4)).unwrap();
+    let input = vec![90u8; 20 * 14 * 3];
+
+    let output = Pipeline::new(&x4).then(&x2).process(&input, 20, 14).expect("Failed to run 8x pipeline");
+    assert_eq!(output.len(), 160 * 112 * 3);
+
+    let output = Pipeline::new(&x4).then(&x4).downscale(2).process(&input, 20, 14).expect("Failed to run 16x/2 pipeline");
+    assert_eq!(output.len(), 160 * 112 * 3);
+
+    let result = Pipeline::new(&x2).downscale(4).process(&input, 20, 14);
+    assert!(matches!(result, Err(Error::InvalidPipeline(_))));
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn region_enhancer_sees_whole_image() {
+    use std::sync::{Arc, Mutex};
+    use realesrgan_rs::{Error, Pipeline, Region, RegionEnhancer, Tiler};
+
+    /// Records every call and paints the crops white.
+    struct Recorder(Arc<Mutex<Vec<(usize, usize)>>>);
+
+    impl RegionEnhancer for Recorder {
+        fn detect(&self, _input: &[u8], width: usize, height: usize, _channels: usize) -> Result<Vec<Region>, Error> {
+            self.0.lock().unwrap().push((width, height));
+            // straddles the seam between the first two 16 pixel tiles
+            Ok(vec![Region { x: 10, y: 4, width: 12, height: 8 }])
+        }
+
+        fn enhance(&self, crop: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<u8>, Error> {
+            Ok(vec![255; crop.len()])
+        }
+    }
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2).tilesize(16))
+        .unwrap()
+        .region_enhancer(Recorder(calls.clone()));
+    let input = vec![0u8; 40 * 20 * 3];
+
+    let white_box = |output: &[u8], scale: usize| {
+        // the box without its feathered edge
+        (6 * scale..10 * scale).all(|y| (14 * scale..18 * scale).all(|x| output[(y * 40 * scale + x) * 3] == 255))
+    };
+
+    let output = realesrgan.process(&input, 40, 20).unwrap();
+    assert_eq!(*calls.lock().unwrap(), [(40, 20)]);
+    assert!(white_box(&output, 2));
+
+    calls.lock().unwrap().clear();
+    let pipeline = Pipeline::new(&realesrgan).then(&realesrgan).tiler(Tiler::new(16, 16).padding(4));
+    let output = pipeline.process(&input, 40, 20).unwrap();
+    assert_eq!(*calls.lock().unwrap(), [(40, 20), (40, 20)]);
+    assert!(white_box(&output, 4));
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn restore_mode_keeps_size() {
+    use realesrgan_rs::OptionsRestoreMode;
+
+    let input = vec![200u8; 21 * 15 * 3];
+    for restore_mode in [OptionsRestoreMode::DownscaleOutput, OptionsRestoreMode::DownscaleInput] {
+        let realesrgan = RealEsrgan::new(
+            Options::default()
+                .model(OptionsModel::RealESRAnimeVideoV3x4)
+                .restore_mode(restore_mode),
+        )
+        .unwrap();
+        assert_eq!(realesrgan.output_scale(), 1);
+
+        let output = realesrgan.process(&input, 21, 15).expect("Failed to restore image");
+        assert_eq!(output.len(), input.len(), "{restore_mode:?}");
+    }
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+#[cfg(feature = "model-realesrgan-plus")]
+fn weak_model_interpolates_weights() {
+    use realesrgan_rs::Error;
+
+    let (param, bin) = OptionsModel::RealESRAnimeVideoV3x4.get_bytes();
+    let input: Vec<u8> = (0..20 * 14 * 3).map(|i| (i * 7 % 251) as u8).collect();
+
+    // a model interpolated with itself is that model, with no pixel blending on top
+    let full = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x4)).unwrap();
+    let paired = RealEsrgan::new(
+        Options::default()
+            .model(OptionsModel::RealESRAnimeVideoV3x4)
+            .weak_model_bytes(param, bin)
+            .strength(0.5),
+    )
+    .expect("Failed to interpolate the paired models");
+    assert_eq!(paired.process(&input, 20, 14).unwrap(), full.process(&input, 20, 14).unwrap());
+
+    let (other_param, other_bin) = OptionsModel::RealESRGANPlusx4.get_bytes();
+    let result = RealEsrgan::new(
+        Options::default()
+            .model(OptionsModel::RealESRAnimeVideoV3x4)
+            .weak_model_bytes(other_param, other_bin)
+            .strength(0.5),
+    );
+    assert!(matches!(result, Err(Error::IncompatibleModels(_))));
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+#[cfg(feature = "image")]
+fn gray_images_keep_their_color_type() {
+    use image::{ColorType, DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma};
+    use realesrgan_rs::Pipeline;
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+
+    let gray = DynamicImage::ImageLuma8(GrayImage::from_fn(12, 9, |x, y| Luma([(x * 20 + y) as u8])));
+    let gray_alpha = DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(12, 9, |x, _| image::LumaA([90, if x < 6 { 0 } else { 255 }])));
+    let gray16 = DynamicImage::ImageLuma16(ImageBuffer::from_fn(12, 9, |x, _| Luma([x as u16 * 5000])));
+
+    for (image, color) in [(gray, ColorType::L8), (gray_alpha, ColorType::La8), (gray16, ColorType::L16)] {
+        let upscaled = realesrgan.process_image(&image).expect("Failed to upscale gray image");
+        assert_eq!(upscaled.color(), color);
+        assert_eq!((upscaled.width(), upscaled.height()), (24, 18));
+
+        let chained = Pipeline::new(&realesrgan).then(&realesrgan).process_image(&image).expect("Failed to chain gray image");
+        assert_eq!(chained.color(), color);
+        assert_eq!((chained.width(), chained.height()), (48, 36));
+    }
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn alpha_modes() {
+    use realesrgan_rs::OptionsAlphaMode;
+
+    // left half opaque red, right half fully transparent
+    let input: Vec<u8> = (0..16 * 10).flat_map(|i| if i % 16 < 8 { [255, 0, 0, 255] } else { [0, 0, 0, 0] }).collect();
+
+    for alpha_mode in [OptionsAlphaMode::Bicubic, OptionsAlphaMode::Nearest, OptionsAlphaMode::Model, OptionsAlphaMode::Premultiplied] {
+        let realesrgan = RealEsrgan::new(
+            Options::default()
+                .model(OptionsModel::RealESRAnimeVideoV3x2)
+                .alpha_mode(alpha_mode),
+        )
+        .unwrap();
+
+        let output = realesrgan.process(&input, 16, 10).expect("Failed to upscale RGBA");
+        assert_eq!(output.len(), 32 * 20 * 4, "{alpha_mode:?}");
+        if alpha_mode == OptionsAlphaMode::Nearest {
+            assert!(output.chunks_exact(4).all(|pixel| pixel[3] == 0 || pixel[3] == 255));
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn model_alpha_skips_color_stages() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use realesrgan_rs::{Error, OptionsAlphaMode, Region, RegionEnhancer};
+
+    struct Counter(Arc<AtomicUsize>);
+
+    impl RegionEnhancer for Counter {
+        fn detect(&self, _input: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<Region>, Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+
+        fn enhance(&self, crop: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<u8>, Error> {
+            Ok(crop.to_vec())
+        }
+    }
+
+    let input: Vec<u8> = (0..16 * 10).flat_map(|i| [200, 40, 90, (i * 13 % 256) as u8]).collect();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let alpha = |strength: f32| {
+        let realesrgan = RealEsrgan::new(
+            Options::default()
+                .model(OptionsModel::RealESRAnimeVideoV3x2)
+                .alpha_mode(OptionsAlphaMode::Model)
+                .strength(strength),
+        )
+        .unwrap()
+        .region_enhancer(Counter(calls.clone()));
+        let output = realesrgan.process(&input, 16, 10).unwrap();
+        output.chunks_exact(4).map(|pixel| pixel[3]).collect::<Vec<u8>>()
+    };
+
+    // strength only blends the color, and the enhancer only runs once per image
+    assert_eq!(alpha(0.0), alpha(1.0));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn bgr_matches_rgb() {
+    use realesrgan_rs::{ImageView, PixelFormat};
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+
+    let rgba: Vec<u8> = (0..14 * 9).flat_map(|i| [(i * 3) as u8, 40, 200, 255]).collect();
+    let bgra: Vec<u8> = rgba.chunks_exact(4).flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]]).collect();
+
+    let from_rgba = realesrgan.process_view(ImageView::new(&rgba, 14, 9, PixelFormat::Rgba)).unwrap();
+    let from_bgra = realesrgan.process_view(ImageView::new(&bgra, 14, 9, PixelFormat::Bgra)).unwrap();
+
+    let swizzled: Vec<u8> = from_bgra.chunks_exact(4).flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]]).collect();
+    assert_eq!(swizzled, from_rgba);
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn premultiplied_view_matches_straight() {
+    use realesrgan_rs::{ImageView, PixelFormat};
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+
+    let straight: Vec<u8> = (0..14 * 9).flat_map(|i| [200, 120, 40, (i * 2) as u8]).collect();
+    let premultiplied: Vec<u8> = straight
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            [0, 1, 2].map(|channel| ((pixel[channel] as u32 * alpha + 127) / 255) as u8).into_iter().chain([pixel[3]])
+        })
+        .collect();
+
+    let from_straight = realesrgan.process_view(ImageView::new(&straight, 14, 9, PixelFormat::Rgba)).unwrap();
+    let from_premultiplied = realesrgan.process_view(ImageView::new(&premultiplied, 14, 9, PixelFormat::RgbaPremultiplied)).unwrap();
+
+    // same pixels once the straight result is premultiplied, up to the rounding of the round trip
+    for (straight, premultiplied) in from_straight.chunks_exact(4).zip(from_premultiplied.chunks_exact(4)) {
+        let alpha = straight[3] as i32;
+        assert_eq!(premultiplied[3], straight[3]);
+        for channel in 0..3 {
+            let expected = (straight[channel] as i32 * alpha + 127) / 255;
+            assert!((premultiplied[channel] as i32 - expected).abs() <= 2, "{straight:?} vs {premultiplied:?}");
+        }
+    }
+}
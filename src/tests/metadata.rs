@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use realesrgan_rs::{Options, OptionsModel, RealEsrgan};
+
+const ICC_PROFILE: &[u8] = b"not a real profile, only carried across";
+
+/// Little-endian TIFF with Orientation = 6 (rotate 90), XResolution = 72/1 and ResolutionUnit = inch.
+fn exif() -> Vec<u8> {
+    let mut exif = b"II*\0".to_vec();
+    exif.extend(8u32.to_le_bytes());
+    exif.extend(3u16.to_le_bytes());
+    for (tag, kind, value) in [(0x0112u16, 3u16, 6u32), (0x011a, 5, 50), (0x0128, 3, 2)] {
+        exif.extend(tag.to_le_bytes());
+        exif.extend(kind.to_le_bytes());
+        exif.extend(1u32.to_le_bytes());
+        exif.extend(value.to_le_bytes());
+    }
+    exif.extend(0u32.to_le_bytes());
+    exif.extend(72u32.to_le_bytes());
+    exif.extend(1u32.to_le_bytes());
+    exif
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn process_file_to_keeps_metadata() {
+    let root = std::env::temp_dir().join("realesrgan_rs_metadata");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let input = root.join("input.png");
+    let output = root.join("output.png");
+
+    let mut info = png::Info::with_size(12, 8);
+    info.color_type = png::ColorType::Rgb;
+    info.bit_depth = png::BitDepth::Eight;
+    info.icc_profile = Some(ICC_PROFILE.into());
+    info.exif_metadata = Some(exif().into());
+    info.pixel_dims = Some(png::PixelDimensions { xppu: 2835, yppu: 2835, unit: png::Unit::Meter });
+    let mut writer = png::Encoder::with_info(BufWriter::new(File::create(&input).unwrap()), info)
+        .unwrap()
+        .write_header()
+        .unwrap();
+    writer.write_image_data(&[90u8; 12 * 8 * 3]).unwrap();
+    writer.finish().unwrap();
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    realesrgan.process_file_to(&input, &output).expect("Failed to upscale file");
+
+    let reader = png::Decoder::new(BufReader::new(File::open(&output).unwrap())).read_info().unwrap();
+    let info = reader.info();
+    assert_eq!((info.width, info.height), (16, 24), "EXIF orientation was not applied");
+    assert_eq!(info.icc_profile.as_deref(), Some(ICC_PROFILE));
+    assert_eq!(info.pixel_dims.map(|dims| dims.xppu), Some(5670));
+
+    let exif = info.exif_metadata.as_deref().unwrap();
+    assert_eq!(u16::from_le_bytes([exif[18], exif[19]]), 1, "Orientation was not cleared");
+    assert_eq!(u32::from_le_bytes(exif[50..54].try_into().unwrap()), 144, "XResolution was not scaled");
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+#[cfg(feature = "color-management")]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn color_managed_round_trip() {
+    let icc_profile = moxcms::ColorProfile::new_display_p3().encode().unwrap();
+    let image = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(12, 8, image::Rgb([120, 110, 100])));
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let upscaled = realesrgan
+        .process_image_with_profile(&image, &icc_profile)
+        .expect("Failed to upscale with a color profile");
+
+    assert_eq!((upscaled.width(), upscaled.height()), (24, 16));
+    let pixel = upscaled.as_rgb8().unwrap().get_pixel(12, 8);
+    for (channel, expected) in pixel.0.iter().zip([120u8, 110, 100]) {
+        assert!(channel.abs_diff(expected) <= 4, "{pixel:?} drifted from the original color");
+    }
+}
+
+#[test]
+#[cfg(feature = "color-management")]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn gray_color_managed_round_trip() {
+    let gray_profile = moxcms::ColorProfile::new_gray_with_gamma(1.8).encode().unwrap();
+    let rgb_profile = moxcms::ColorProfile::new_display_p3().encode().unwrap();
+    let image = image::DynamicImage::ImageLuma8(image::GrayImage::from_pixel(12, 8, image::Luma([110])));
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    for icc_profile in [gray_profile, rgb_profile] {
+        let upscaled = realesrgan
+            .process_image_with_profile(&image, &icc_profile)
+            .expect("Failed to upscale gray with a color profile");
+
+        assert_eq!(upscaled.color(), image::ColorType::L8);
+        assert_eq!((upscaled.width(), upscaled.height()), (24, 16));
+        let pixel = upscaled.as_luma8().unwrap().get_pixel(12, 8);
+        assert!(pixel.0[0].abs_diff(110) <= 4, "{pixel:?} drifted from the original gray");
+    }
+}
@@ -0,0 +1,71 @@
+use realesrgan_rs::{Error, Options, OptionsPrecision, OptionsTileBlend, OptionsTtaMode};
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+#[cfg(feature = "model-realesrgan-plus")]
+fn model_names_respect_the_scale() {
+    use realesrgan_rs::OptionsModel;
+
+    assert_eq!(OptionsModel::from_name("realesr-animevideov3", 3), Some(OptionsModel::RealESRAnimeVideoV3x3));
+    assert_eq!(OptionsModel::from_name("realesr-animevideov3-x2", 2), Some(OptionsModel::RealESRAnimeVideoV3x2));
+    assert_eq!(OptionsModel::from_name("realesr-animevideov3-x2", 4), None);
+    assert_eq!(OptionsModel::from_name("realesrgan-x4plus", 4), Some(OptionsModel::RealESRGANPlusx4));
+    assert_eq!(OptionsModel::from_name("realesrgan-x4plus", 2), None);
+    assert_eq!(OptionsModel::from_name("unknown", 4), None);
+}
+
+#[test]
+fn tile_options_set_their_fields() {
+    let options = Options::default();
+    assert_eq!((options.tile_width, options.tile_height, options.tile_padding), (0, 0, 10));
+    assert_eq!(options.tile_blend, OptionsTileBlend::Crop);
+    assert!(!options.adaptive_tilesize);
+
+    let options = Options::default()
+        .tilesize(64)
+        .tile_height(48)
+        .tile_padding(4)
+        .tile_blend(OptionsTileBlend::Linear)
+        .adaptive_tilesize(true);
+    assert_eq!((options.tile_width, options.tile_height, options.tile_padding), (64, 48, 4));
+    assert_eq!(options.tile_blend, OptionsTileBlend::Linear);
+    assert!(options.adaptive_tilesize);
+}
+
+#[test]
+fn precision_defaults_to_auto() {
+    assert_eq!(Options::default().precision, OptionsPrecision::Auto);
+    assert_eq!(Options::default().precision(OptionsPrecision::Fp16Storage).precision, OptionsPrecision::Fp16Storage);
+}
+
+#[test]
+fn tta_mode_from_bool() {
+    assert_eq!(OptionsTtaMode::from(true), OptionsTtaMode::Full);
+    assert_eq!(OptionsTtaMode::from(false), OptionsTtaMode::Off);
+    assert_eq!(Options::default().tta_mode(true).tta_mode.passes(), 8);
+    assert_eq!(Options::default().tta_mode(OptionsTtaMode::Horizontal).tta_mode.passes(), 2);
+}
+
+#[test]
+fn weak_model_is_optional() {
+    assert!(Options::default().weak_model.is_none());
+
+    let options = Options::default().weak_model_bytes(b"param", b"bin");
+    assert_eq!(options.weak_model, Some((&b"param"[..], &b"bin"[..])));
+}
+
+#[test]
+fn strength_is_clamped() {
+    assert_eq!(Options::default().strength(1.5).strength, 1.0);
+    assert_eq!(Options::default().strength(-0.5).strength, 0.0);
+    assert_eq!(Options::default().strength(0.25).strength, 0.25);
+}
+
+#[test]
+fn load_errors_name_the_layer() {
+    let error = Error::WeightSizeMismatch { layer: Some("conv_3".to_string()) };
+    assert_eq!(error.to_string(), "Model weights do not match the param file (layer: conv_3).");
+
+    let error = Error::PipelineCreationFailed { layer: None };
+    assert_eq!(error.to_string(), "Failed to create a GPU pipeline (layer: unknown).");
+}
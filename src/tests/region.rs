@@ -0,0 +1,71 @@
+use crate::tiler::{gradient, nearest};
+
+use realesrgan_rs::{enhance_regions, Error, Region, RegionEnhancer};
+
+/// Paints every detected box white.
+struct WhiteBoxes(Vec<Region>);
+
+impl RegionEnhancer for WhiteBoxes {
+    fn detect(&self, _input: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<Region>, Error> {
+        Ok(self.0.clone())
+    }
+
+    fn enhance(&self, crop: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<u8>, Error> {
+        Ok(vec![255; crop.len()])
+    }
+}
+
+struct Truncating;
+
+impl RegionEnhancer for Truncating {
+    fn detect(&self, _input: &[u8], width: usize, height: usize, _channels: usize) -> Result<Vec<Region>, Error> {
+        Ok(vec![Region { x: 0, y: 0, width, height }])
+    }
+
+    fn enhance(&self, crop: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<u8>, Error> {
+        Ok(crop[1..].to_vec())
+    }
+}
+
+#[test]
+fn pastes_with_feathered_edges() {
+    let (width, height, channels, scale) = (20, 20, 3, 2);
+    let input = vec![0u8; width * height * channels];
+    let mut output = nearest(&input, width, height, channels, scale);
+
+    let enhancer = WhiteBoxes(vec![Region { x: 4, y: 4, width: 8, height: 8 }]);
+    enhance_regions(&enhancer, &input, &mut output, width, height, channels, scale).unwrap();
+
+    let pixel = |x: usize, y: usize| output[(y * width * scale + x) * channels];
+    // the box covers 8..24 in output pixels, feathered over 2 pixels
+    assert_eq!(pixel(7, 16), 0);
+    assert_eq!(pixel(8, 16), 64);
+    assert_eq!(pixel(9, 16), 191);
+    assert_eq!(pixel(16, 16), 255);
+    assert_eq!(pixel(23, 16), 64);
+    assert_eq!(pixel(24, 16), 0);
+}
+
+#[test]
+fn clips_boxes_to_the_image() {
+    let (width, height, channels) = (10, 6, 4);
+    let input = gradient(width, height, channels);
+    let mut output = input.clone();
+
+    let enhancer = WhiteBoxes(vec![
+        Region { x: 8, y: 4, width: 10, height: 10 },
+        Region { x: 20, y: 0, width: 5, height: 5 },
+    ]);
+    enhance_regions(&enhancer, &input, &mut output, width, height, channels, 1).unwrap();
+
+    assert_eq!(output[..4 * width * channels], input[..4 * width * channels]);
+    assert_ne!(output, input);
+}
+
+#[test]
+fn rejects_resized_crops() {
+    let input = gradient(4, 4, 3);
+    let mut output = input.clone();
+    let result = enhance_regions(&Truncating, &input, &mut output, 4, 4, 3, 1);
+    assert!(matches!(result, Err(Error::InvalidTileOutput { .. })));
+}
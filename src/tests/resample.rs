@@ -0,0 +1,44 @@
+use realesrgan_rs::{OptionsResampleFilter, Resampler, TileBackend, Tiler};
+
+const FILTERS: [OptionsResampleFilter; 2] = [OptionsResampleFilter::Bicubic, OptionsResampleFilter::Lanczos];
+
+#[test]
+fn flat_image_stays_flat() {
+    let input = vec![77u8; 9 * 5 * 4];
+    for filter in FILTERS {
+        let output = Resampler::new(3, filter).upscale(&input, 9, 5, 4);
+        assert_eq!(output.len(), 27 * 15 * 4);
+        assert!(output.iter().all(|value| *value == 77), "{filter:?}");
+    }
+}
+
+#[test]
+fn follows_a_ramp() {
+    let width = 32;
+    let input: Vec<u8> = (0..width).map(|x| (x * 8) as u8).collect();
+    let output = Resampler::new(2, OptionsResampleFilter::Bicubic).upscale(&input, width, 1, 1);
+
+    // away from the edges, Catmull-Rom reproduces a linear ramp exactly
+    for (x, value) in output.iter().enumerate().take(2 * width - 8).skip(8) {
+        let expected = (x as f32 + 0.5) / 2.0 - 0.5;
+        assert!((*value as f32 - expected * 8.0).abs() <= 1.0, "{x}: {value}");
+    }
+}
+
+#[test]
+fn tiles_like_any_backend() {
+    let (width, height, channels) = (40, 30, 3);
+    let input: Vec<u8> = (0..width * height * channels).map(|i| (i * 13 % 256) as u8).collect();
+    let resampler = Resampler::new(2, OptionsResampleFilter::Lanczos);
+
+    let whole = resampler.process_tile(&input, width, height, channels).unwrap();
+    let tiled = Tiler::new(16, 16).padding(6).process(&resampler, &input, width, height, channels).unwrap();
+
+    // the tiler mirrors the image borders where the resampler clamps, so only compare the interior
+    let output_width = width * 2;
+    for y in 8..height * 2 - 8 {
+        let row = (y * output_width + 8) * channels..(y * output_width + output_width - 8) * channels;
+        assert_eq!(tiled[row.clone()], whole[row], "row {y}");
+    }
+    assert!(resampler.process_tile(&input[1..], width, height, channels).is_err());
+}
@@ -0,0 +1,123 @@
+use realesrgan_rs::{Error, OptionsTileBlend, Tile, TileBackend, Tiler};
+
+pub(crate) struct NearestBackend {
+    pub(crate) scale: usize,
+}
+
+impl TileBackend for NearestBackend {
+    fn scale_factor(&self) -> usize {
+        self.scale
+    }
+
+    fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        Ok(nearest(input, width, height, channels, self.scale))
+    }
+}
+
+pub(crate) fn nearest(input: &[u8], width: usize, height: usize, channels: usize, scale: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(width * scale * height * scale * channels);
+    for y in 0..height * scale {
+        for x in 0..width * scale {
+            let offset = ((y / scale) * width + x / scale) * channels;
+            output.extend_from_slice(&input[offset..offset + channels]);
+        }
+    }
+    output
+}
+
+pub(crate) fn gradient(width: usize, height: usize, channels: usize) -> Vec<u8> {
+    (0..width * height * channels).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn tiles_cover_image() {
+    let tiles = Tiler::new(32, 20).tiles(70, 45);
+    assert_eq!(tiles.len(), 9);
+    assert_eq!(tiles[0], Tile { x: 0, y: 0, width: 32, height: 20 });
+    assert_eq!(tiles[8], Tile { x: 64, y: 40, width: 6, height: 5 });
+
+    let area: usize = tiles.iter().map(|tile| tile.width * tile.height).sum();
+    assert_eq!(area, 70 * 45);
+}
+
+#[test]
+fn extract_reflects_borders() {
+    let input: Vec<u8> = (0..16).collect();
+    let tiler = Tiler::new(2, 2).padding(1);
+    let tile = Tile { x: 0, y: 0, width: 2, height: 2 };
+    let padded = tiler.extract(&input, 4, 4, 1, &tile);
+
+    assert_eq!(padded, vec![
+        5, 4, 5, 6,
+        1, 0, 1, 2,
+        5, 4, 5, 6,
+        9, 8, 9, 10,
+    ]);
+}
+
+#[test]
+fn crop_matches_whole_image() {
+    let (width, height, channels) = (45, 37, 3);
+    let input = gradient(width, height, channels);
+    let backend = NearestBackend { scale: 2 };
+
+    let output = Tiler::new(16, 12)
+        .padding(4)
+        .process(&backend, &input, width, height, channels)
+        .unwrap();
+
+    assert_eq!(output, nearest(&input, width, height, channels, 2));
+}
+
+#[test]
+fn linear_blend_matches_whole_image() {
+    let (width, height, channels) = (45, 37, 4);
+    let input = gradient(width, height, channels);
+    let backend = NearestBackend { scale: 3 };
+
+    let output = Tiler::new(10, 10)
+        .padding(6)
+        .blend(OptionsTileBlend::Linear)
+        .process(&backend, &input, width, height, channels)
+        .unwrap();
+
+    assert_eq!(output, nearest(&input, width, height, channels, 3));
+}
+
+#[test]
+fn rejects_wrong_input_length() {
+    let backend = NearestBackend { scale: 2 };
+    let result = Tiler::new(8, 8).process(&backend, &[0u8; 10], 4, 4, 1);
+    assert!(matches!(result, Err(Error::InvalidInput { expected_length: 16, actual_length: 10 })));
+}
+
+#[test]
+fn padding_wider_than_the_tile() {
+    struct CountingBackend {
+        inner: NearestBackend,
+        calls: std::cell::Cell<usize>,
+    }
+
+    impl TileBackend for CountingBackend {
+        fn scale_factor(&self) -> usize {
+            self.inner.scale_factor()
+        }
+
+        fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.process_tile(input, width, height, channels)
+        }
+    }
+
+    let (width, height, channels) = (45, 37, 3);
+    let input = gradient(width, height, channels);
+    let backend = CountingBackend { inner: NearestBackend { scale: 2 }, calls: std::cell::Cell::new(0) };
+
+    let output = Tiler::new(16, 16)
+        .padding(16)
+        .process(&backend, &input, width, height, channels)
+        .unwrap();
+
+    assert_eq!(output, nearest(&input, width, height, channels, 2));
+    assert_eq!(backend.calls.get(), 3 * 3);
+}
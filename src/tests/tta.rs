@@ -0,0 +1,94 @@
+use crate::tiler::{gradient, nearest, NearestBackend};
+
+use std::cell::RefCell;
+
+use realesrgan_rs::{Error, OptionsTtaMerge, OptionsTtaMode, TileBackend, Tiler, Tta};
+
+/// Nearest-neighbour upscaling that also records the shape of every tile it sees.
+struct RecordingBackend {
+    shapes: RefCell<Vec<(usize, usize)>>,
+}
+
+impl TileBackend for RecordingBackend {
+    fn scale_factor(&self) -> usize {
+        2
+    }
+
+    fn process_tile(&self, input: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Error> {
+        self.shapes.borrow_mut().push((width, height));
+        Ok(nearest(input, width, height, channels, 2))
+    }
+}
+
+/// Brightens the top-left pixel of whatever it is given, so every pass disagrees somewhere.
+struct CornerBackend;
+
+impl TileBackend for CornerBackend {
+    fn scale_factor(&self) -> usize {
+        1
+    }
+
+    fn process_tile(&self, input: &[u8], _width: usize, _height: usize, _channels: usize) -> Result<Vec<u8>, Error> {
+        let mut output = input.to_vec();
+        output[0] = 255;
+        Ok(output)
+    }
+}
+
+#[test]
+fn every_mode_undoes_its_transforms() {
+    let (width, height, channels) = (7, 5, 3);
+    let input = gradient(width, height, channels);
+    let expected = nearest(&input, width, height, channels, 2);
+    let backend = NearestBackend { scale: 2 };
+
+    for mode in [OptionsTtaMode::Off, OptionsTtaMode::Horizontal, OptionsTtaMode::Flips, OptionsTtaMode::Full] {
+        for merge in [OptionsTtaMerge::Mean, OptionsTtaMerge::Median] {
+            let tta = Tta::new(&backend, mode).merge(merge);
+            let output = tta.process_tile(&input, width, height, channels).unwrap();
+            assert_eq!(output, expected, "{mode:?} {merge:?}");
+        }
+    }
+}
+
+#[test]
+fn passes_match_mode() {
+    let backend = RecordingBackend { shapes: RefCell::new(Vec::new()) };
+    let input = gradient(6, 4, 4);
+
+    for mode in [OptionsTtaMode::Off, OptionsTtaMode::Horizontal, OptionsTtaMode::Flips, OptionsTtaMode::Full] {
+        backend.shapes.borrow_mut().clear();
+        Tta::new(&backend, mode).process_tile(&input, 6, 4, 4).unwrap();
+        let shapes = backend.shapes.borrow();
+        assert_eq!(shapes.len(), mode.passes());
+        let transposed = shapes.iter().filter(|shape| **shape == (4, 6)).count();
+        assert_eq!(transposed, if mode == OptionsTtaMode::Full { 4 } else { 0 });
+    }
+}
+
+#[test]
+fn median_ignores_outlier() {
+    let input = vec![100u8; 4 * 4];
+
+    let mean = Tta::new(&CornerBackend, OptionsTtaMode::Flips).process_tile(&input, 4, 4, 1).unwrap();
+    let median = Tta::new(&CornerBackend, OptionsTtaMode::Flips)
+        .merge(OptionsTtaMerge::Median)
+        .process_tile(&input, 4, 4, 1)
+        .unwrap();
+
+    // each corner is brightened by exactly one of the four passes
+    assert_eq!(mean[0], 139);
+    assert_eq!(median[0], 100);
+    assert!(median.iter().all(|value| *value == 100));
+}
+
+#[test]
+fn works_inside_tiler() {
+    let (width, height, channels) = (37, 23, 3);
+    let input = gradient(width, height, channels);
+    let backend = NearestBackend { scale: 2 };
+    let tta = Tta::new(&backend, OptionsTtaMode::Full);
+
+    let output = Tiler::new(16, 16).padding(4).process(&tta, &input, width, height, channels).unwrap();
+    assert_eq!(output, nearest(&input, width, height, channels, 2));
+}
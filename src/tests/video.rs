@@ -0,0 +1,73 @@
+use realesrgan_rs::{RealEsrgan, Options, OptionsModel, VideoUpscaler};
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn video_file() {
+    let root = std::env::temp_dir().join("realesrgan_rs_video");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let input = root.join("input.mkv");
+    let output = root.join("output.mkv");
+
+    let status = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-f", "lavfi", "-i", "testsrc=size=32x24:rate=5", "-frames:v", "6"])
+        .arg(&input)
+        .status()
+        .expect("Failed to run ffmpeg");
+    assert!(status.success(), "Failed to create test video");
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let mut frames = Vec::new();
+    let report = VideoUpscaler::new(&realesrgan)
+        .codec("ffv1")
+        .pixel_format("rgb24")
+        .progress(|progress| frames.push(progress.frame))
+        .run(&input, &output)
+        .expect("Failed to upscale video");
+    assert_eq!(report.frames, 6);
+    assert_eq!(frames, (1..=6).collect::<Vec<_>>());
+
+    let info = VideoUpscaler::new(&realesrgan).probe(&output).expect("Failed to probe output");
+    assert_eq!((info.width, info.height, info.frames), (64, 48, Some(6)));
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+#[cfg(feature = "model-realesr-animevideov3")]
+fn rotated_video_is_upright() {
+    let root = std::env::temp_dir().join("realesrgan_rs_rotated_video");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let source = root.join("source.mkv");
+    let input = root.join("input.mp4");
+    let output = root.join("output.mkv");
+
+    let status = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-f", "lavfi", "-i", "testsrc=size=32x24:rate=5", "-frames:v", "3"])
+        .arg(&source)
+        .status()
+        .expect("Failed to run ffmpeg");
+    assert!(status.success(), "Failed to create test video");
+    let status = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-display_rotation", "90", "-i"])
+        .arg(&source)
+        .args(["-c", "copy"])
+        .arg(&input)
+        .status()
+        .expect("Failed to run ffmpeg");
+    assert!(status.success(), "Failed to rotate test video");
+
+    let realesrgan = RealEsrgan::new(Options::default().model(OptionsModel::RealESRAnimeVideoV3x2)).unwrap();
+    let mut upscaler = VideoUpscaler::new(&realesrgan).codec("ffv1").pixel_format("rgb24");
+    let info = upscaler.probe(&input).expect("Failed to probe input");
+    assert_eq!((info.width, info.height, info.frames), (24, 32, Some(3)));
+    assert_eq!(info.rotation % 180, 90);
+
+    let report = upscaler.run(&input, &output).expect("Failed to upscale rotated video");
+    assert_eq!(report.frames, 3);
+    let info = upscaler.probe(&output).expect("Failed to probe output");
+    assert_eq!((info.width, info.height, info.rotation), (48, 64, 0));
+
+    let _ = std::fs::remove_dir_all(&root);
+}
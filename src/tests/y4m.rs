@@ -0,0 +1,92 @@
+use realesrgan_rs::{ChromaSampling, ColorMatrix, ColorRange, Error, Y4mHeader, Y4mReader, Y4mWriter, YuvFrame};
+
+fn gradient(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height * 3).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn known_colors() {
+    let red = YuvFrame::from_rgb(&[255, 0, 0], 1, 1, ChromaSampling::Yuv444, ColorMatrix::Bt601, ColorRange::Limited);
+    assert_eq!(red.data, vec![81, 90, 240]);
+
+    let red = YuvFrame::from_rgb(&[255, 0, 0], 1, 1, ChromaSampling::Yuv444, ColorMatrix::Bt709, ColorRange::Limited);
+    assert_eq!(red.data, vec![63, 102, 240]);
+
+    let white = YuvFrame::from_rgb(&[255, 255, 255], 1, 1, ChromaSampling::Yuv444, ColorMatrix::Bt709, ColorRange::Full);
+    assert_eq!(white.data, vec![255, 128, 128]);
+}
+
+#[test]
+fn rgb_round_trip() {
+    let (width, height) = (9, 7);
+    let rgb = gradient(width, height);
+
+    for matrix in [ColorMatrix::Bt601, ColorMatrix::Bt709] {
+        for range in [ColorRange::Limited, ColorRange::Full] {
+            let frame = YuvFrame::from_rgb(&rgb, width, height, ChromaSampling::Yuv444, matrix, range);
+            assert_eq!(frame.data.len(), frame.expected_length());
+
+            let output = frame.to_rgb(matrix, range);
+            let error = rgb.iter().zip(&output).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+            assert!(error <= 3, "{matrix:?} {range:?} error {error}");
+        }
+    }
+}
+
+#[test]
+fn subsampled_planes() {
+    let frame = YuvFrame::from_rgb(&[200; 5 * 3 * 3], 5, 3, ChromaSampling::Yuv420, ColorMatrix::Bt601, ColorRange::Limited);
+    let (y, u, v) = frame.planes();
+    assert_eq!((y.len(), u.len(), v.len()), (15, 6, 6));
+    assert!(frame.to_rgb(ColorMatrix::Bt601, ColorRange::Limited).iter().all(|value| value.abs_diff(200) <= 1));
+}
+
+#[test]
+fn stream_round_trip() {
+    let mut header = Y4mHeader::new(5, 3, (24000, 1001), ChromaSampling::Yuv420);
+    header.colorspace = Some("420mpeg2".to_string());
+    header.range = Some(ColorRange::Full);
+    header.parameters = vec!["Ip".to_string(), "A1:1".to_string()];
+
+    let frames: Vec<_> = (0..3u8)
+        .map(|value| YuvFrame::new(vec![value; ChromaSampling::Yuv420.frame_length(5, 3)], 5, 3, ChromaSampling::Yuv420))
+        .collect();
+
+    let mut writer = Y4mWriter::new(Vec::new(), header.clone()).unwrap();
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    let stream = writer.into_inner();
+    assert!(stream.starts_with(b"YUV4MPEG2 W5 H3 F24000:1001 C420mpeg2 Ip A1:1 XCOLORRANGE=FULL\nFRAME\n"));
+
+    let reader = Y4mReader::new(stream.as_slice()).unwrap();
+    assert_eq!(reader.header(), &header);
+    let read: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, frames);
+}
+
+#[test]
+fn rejects_unsupported_streams() {
+    let result = Y4mReader::new(b"YUV4MPEG2 W4 H4 F25:1 Cmono\n".as_slice());
+    assert!(matches!(result, Err(Error::Y4mParseFailed(_))));
+
+    let mut reader = Y4mReader::new(b"YUV4MPEG2 W4 H4 F25:1 C444\nFRAME\n\x00\x01".as_slice()).unwrap();
+    assert!(matches!(reader.read_frame(), Err(Error::Y4mIoFailed(_))));
+}
+
+#[test]
+fn keeps_chroma_siting() {
+    let write = |header: Y4mHeader| Y4mWriter::new(Vec::new(), header).unwrap().into_inner();
+
+    let reader = Y4mReader::new(b"YUV4MPEG2 W4 H4 F25:1 C420paldv\n".as_slice()).unwrap();
+    assert_eq!(reader.header().colorspace.as_deref(), Some("420paldv"));
+    assert!(write(reader.header().scaled(2)).starts_with(b"YUV4MPEG2 W8 H8 F25:1 C420paldv\n"));
+
+    let header = Y4mHeader::new(4, 4, (25, 1), ChromaSampling::Yuv420);
+    assert!(write(header).starts_with(b"YUV4MPEG2 W4 H4 F25:1 C420jpeg\n"));
+
+    // a tag that no longer fits the sampling falls back to its default
+    let mut header = Y4mHeader::new(4, 4, (25, 1), ChromaSampling::Yuv444);
+    header.colorspace = Some("420mpeg2".to_string());
+    assert!(write(header).starts_with(b"YUV4MPEG2 W4 H4 F25:1 C444\n"));
+}